@@ -0,0 +1,39 @@
+//! Runs of neighbouring searches, pinned a group to a core, vs. spread over the blocking threads.
+//!
+//! Run with `cargo bench --bench affinity_groups`. Pinning only pays off where moving between
+//! cores is expensive, like a machine with more than one socket, where the caches and memory near
+//! one core are a long way from the others. On a single socket laptop, expect little difference,
+//! and none at all with a single core.
+
+use async_await::cpu_affinity::{get_available_cores, run_with_affinity_groups, AffinityGroup};
+use async_await::spawn_with_handle;
+use std::time::Instant;
+use tokio::runtime::Builder;
+
+const GROUP_SIZE: u64 = 5;
+
+/// A group of [GROUP_SIZE] searches for neighbouring primes near the 200,000th for each core,
+/// pinned to that core if `pinned` is set.
+fn groups(cores: &[usize], pinned: bool) -> Vec<AffinityGroup> {
+    cores
+        .iter()
+        .enumerate()
+        .map(|(i, &core)| {
+            let start = 200_000 + 10_000 * i as u64;
+            let tasks = (0..GROUP_SIZE).map(|j| (i as u64 * GROUP_SIZE + j, start + j)).collect();
+            AffinityGroup { tasks, preferred_core: if pinned { Some(core) } else { None } }
+        })
+        .collect()
+}
+
+fn main() {
+    let cores = get_available_cores().into_iter().map(|c| c.id).collect::<Vec<_>>();
+    let rt = Builder::new().blocking_threads(cores.len().max(1)).build().expect("Couldn't build the runtime");
+    for &pinned in &[false, true] {
+        let groups = groups(&cores, pinned);
+        let t = Instant::now();
+        let results = rt.block_on(async { spawn_with_handle(run_with_affinity_groups(groups)).await });
+        let label = if pinned { "Pinned a group to a core" } else { "On the blocking threads" };
+        println!("{:25} {} searches in {:8.3}s", label, results.len(), t.elapsed().as_secs_f64());
+    }
+}
@@ -0,0 +1,34 @@
+//! How much slower [is_prime_aks] is than deterministic Miller-Rabin, on primes of a few sizes.
+//! Primes are the worst case for AKS: composites this small all have a factor below its modulus
+//! `r`, so they're caught long before the polynomial checks.
+//!
+//! Run with `cargo bench --bench aks`.
+
+use async_await::primality::{is_prime_aks, is_prime_fast};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Primes just past the 65536 that [is_prime_fast] has a lookup table for, so that it has to run
+/// Miller-Rabin too.
+const PRIMES: [u64; 3] = [65_537, 100_003, 1_000_003];
+
+const MILLER_RABIN_ROUNDS: u32 = 100_000;
+
+fn time<F: FnMut()>(rounds: u32, mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..rounds {
+        f();
+    }
+    start.elapsed() / rounds
+}
+
+fn main() {
+    println!("{:>10}  {:>12}  {:>12}  {:>8}", "n", "AKS", "Miller-Rabin", "ratio");
+    for &n in &PRIMES {
+        let aks = time(1, || assert!(is_prime_aks(black_box(n))));
+        let miller_rabin = time(MILLER_RABIN_ROUNDS, || assert!(is_prime_fast(black_box(n))));
+        let ratio = aks.as_secs_f64() / miller_rabin.as_secs_f64();
+        let (aks_ms, miller_rabin_us) = (aks.as_secs_f64() * 1e3, miller_rabin.as_secs_f64() * 1e6);
+        println!("{:>10}  {:>10.3}ms  {:>10.3}µs  {:>7.0}×", n, aks_ms, miller_rabin_us, ratio);
+    }
+}
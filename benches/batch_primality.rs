@@ -0,0 +1,45 @@
+//! How much faster is classifying a batch of candidates with `batch_is_prime` than calling
+//! `is_prime_fast` on each one?
+//!
+//! Run with `cargo bench --bench batch_primality`.
+
+use async_await::primality::{batch_is_prime, is_prime_fast};
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 100;
+
+/// 10,000 odd numbers just above 10^9, from a simple linear congruential generator so every run
+/// is the same.
+fn candidates() -> Vec<u64> {
+    let mut x = 12345u64;
+    (0..10_000)
+        .map(|_| {
+            x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (1_000_000_000 + (x >> 33) % 1_000_000) | 1
+        })
+        .collect()
+}
+
+/// Run `f` [ROUNDS] times, and return its last result along with the average time it took.
+fn time<T, F: FnMut() -> T>(mut f: F) -> (T, Duration) {
+    let t = Instant::now();
+    let mut result = f();
+    for _ in 1..ROUNDS {
+        result = f();
+    }
+    (result, t.elapsed() / ROUNDS)
+}
+
+fn main() {
+    let candidates = candidates();
+    let (individual, individual_time) = time(|| candidates.iter().map(|&n| is_prime_fast(n)).collect::<Vec<_>>());
+    let primes = individual.iter().filter(|&&p| p).count();
+    let (batch, batch_time) = time(|| batch_is_prime(&candidates));
+    assert_eq!(individual, batch);
+    let rate = |d: Duration| candidates.len() as f64 / d.as_secs_f64();
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    println!("Classifying {} candidates near 10^9, {} primes among them:", candidates.len(), primes);
+    println!("is_prime_fast each: {:8.3}ms ({:10.0} candidates/s)", ms(individual_time), rate(individual_time));
+    println!("batch_is_prime:     {:8.3}ms ({:10.0} candidates/s)", ms(batch_time), rate(batch_time));
+    println!("The batch is {:.0}% faster", 100.0 * (rate(batch_time) / rate(individual_time) - 1.0));
+}
@@ -0,0 +1,58 @@
+//! 100 tiny blocking jobs, each handed to `blocking` on its own, vs. all handed over at once with
+//! `blocking_scope`.
+//!
+//! Run with `cargo bench --bench blocking_scope`.
+
+use async_await::blocking_scope::blocking_scope;
+use async_await::primality::is_prime_fast;
+use async_await::spawn_with_handle;
+use futures::future::{join_all, poll_fn};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::runtime::{Builder, Runtime};
+use tokio_executor::threadpool::blocking;
+
+const JOBS: u64 = 100;
+const ROUNDS: u32 = 100;
+
+/// The candidates to test: 100 odd numbers just above 10^9.
+fn candidates() -> Vec<u64> {
+    (0..JOBS).map(|i| 1_000_000_001 + 2 * i).collect()
+}
+
+async fn individually() -> Vec<bool> {
+    let tests = candidates().into_iter().map(|n| {
+        spawn_with_handle(async move { poll_fn(|_| blocking(|| is_prime_fast(n))).await.expect("Couldn't block") })
+    });
+    join_all(tests.collect::<Vec<_>>()).await
+}
+
+async fn scoped() -> Vec<bool> {
+    let tests = candidates().into_iter().map(|n| move || is_prime_fast(n)).collect();
+    blocking_scope(tests).await
+}
+
+/// Run the future `f` makes [ROUNDS] times, and return its last output along with the average time
+/// it took.
+fn time<F, Fut>(rt: &Runtime, f: F) -> (Vec<bool>, Duration)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Vec<bool>> + Send + 'static,
+{
+    let t = Instant::now();
+    let mut output = Vec::new();
+    for _ in 0..ROUNDS {
+        output = rt.block_on(async { spawn_with_handle(f()).await });
+    }
+    (output, t.elapsed() / ROUNDS)
+}
+
+fn main() {
+    let rt = Builder::new().core_threads(1).blocking_threads(5).build().expect("Couldn't build the runtime");
+    let (individual, individual_time) = time(&rt, individually);
+    let (batch, batch_time) = time(&rt, scoped);
+    assert_eq!(individual, batch);
+    println!("{} primality tests near 10^9, averaged over {} rounds:", JOBS, ROUNDS);
+    println!("{} blocking calls:   {:8.1}µs", JOBS, individual_time.as_secs_f64() * 1e6);
+    println!("One blocking_scope: {:8.1}µs", batch_time.as_secs_f64() * 1e6);
+}
@@ -0,0 +1,52 @@
+//! Collecting 100 quick search results over a `crossbeam_channel` vs. a Tokio `mpsc` channel.
+//!
+//! Run with `cargo bench --bench channel_comparison`. Each search only takes a millisecond or two,
+//! so the channels make up a fair part of the run.
+
+use async_await::channel_comparison::{collect_via_crossbeam, collect_via_tokio_mpsc};
+use async_await::{spawn_with_handle, PrimeResult};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::runtime::{Builder, Runtime};
+
+const TASKS: u64 = 100;
+const ROUNDS: u32 = 20;
+
+/// 100 searches for primes around the 10000th, which each take a millisecond or two.
+fn tasks() -> Vec<(u64, u64)> {
+    (0..TASKS).map(|i| (i, 9_900 + i)).collect()
+}
+
+/// Collect the results with `collect` [ROUNDS] times. Returns the results of the last round, and
+/// the average time a round took.
+fn run<F, Fut>(rt: &Runtime, collect: F) -> (Vec<PrimeResult>, Duration)
+where
+    F: Fn(Vec<(u64, u64)>) -> Fut,
+    Fut: Future<Output = Vec<PrimeResult>> + Send + 'static,
+{
+    let t = Instant::now();
+    let mut results = Vec::new();
+    for _ in 0..ROUNDS {
+        results = rt.block_on(async { spawn_with_handle(collect(tasks())).await });
+    }
+    (results, t.elapsed() / ROUNDS)
+}
+
+fn main() {
+    let rt = Builder::new().core_threads(1).blocking_threads(5).build().expect("Couldn't build the runtime");
+    let (crossbeam, crossbeam_time) = run(&rt, collect_via_crossbeam);
+    let (tokio, tokio_time) = run(&rt, collect_via_tokio_mpsc);
+
+    let sorted_values = |results: &[PrimeResult]| {
+        let mut results = results.iter().map(|r| (r.id, r.value)).collect::<Vec<_>>();
+        results.sort_unstable();
+        results
+    };
+    assert_eq!(sorted_values(&crossbeam), sorted_values(&tokio));
+    let search_time = crossbeam.iter().map(|r| r.elapsed).sum::<Duration>() / TASKS as u32;
+    let rate = |d: Duration| TASKS as f64 / d.as_secs_f64();
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    println!("{} searches of about {:.2}ms each, averaged over {} rounds:", TASKS, ms(search_time), ROUNDS);
+    println!("crossbeam_channel: {:8.2}ms per round ({:6.0} results/s)", ms(crossbeam_time), rate(crossbeam_time));
+    println!("tokio mpsc:        {:8.2}ms per round ({:6.0} results/s)", ms(tokio_time), rate(tokio_time));
+}
@@ -0,0 +1,29 @@
+//! The first 100,000 primes from `LazyPrimeSieve` vs. `sieve_primes`.
+//!
+//! Run with `cargo bench --bench lazy_sieve`. The lazy sieve doesn't need to know where to stop,
+//! but it pays for that with a hash map lookup for every number, where `sieve_primes` just walks
+//! along an array.
+
+use async_await::lazy_prime_sieve::LazyPrimeSieve;
+use async_await::sieve::sieve_primes;
+use std::time::Instant;
+
+const COUNT: usize = 100_000;
+/// The 100,000th prime
+const LIMIT: u64 = 1_299_709;
+
+fn main() {
+    let t = Instant::now();
+    let lazy = LazyPrimeSieve::new().take(COUNT).collect::<Vec<_>>();
+    let lazy_time = t.elapsed();
+
+    let t = Instant::now();
+    let eager = sieve_primes(LIMIT);
+    let eager_time = t.elapsed();
+
+    assert_eq!(lazy, eager);
+    println!("The first {} primes:", COUNT);
+    println!("LazyPrimeSieve: {:8.3}ms", lazy_time.as_secs_f64() * 1000.0);
+    println!("sieve_primes:   {:8.3}ms", eager_time.as_secs_f64() * 1000.0);
+    println!("The lazy sieve is {:.1}x slower", lazy_time.as_secs_f64() / eager_time.as_secs_f64());
+}
@@ -0,0 +1,35 @@
+//! Testing several candidates at once vs. one after another, for a search too big for the table.
+//!
+//! Run with `cargo bench --bench parallel_search`. The speedup depends on how many cores you have:
+//! with a single core, the searches just take turns and the bookkeeping makes it a bit slower.
+
+use async_await::find_nth_prime;
+use async_await::parallel_search::find_nth_prime_concurrent_candidates;
+use async_await::spawn_with_handle;
+use std::time::Instant;
+use tokio::runtime::Builder;
+
+const N: u64 = 300_000;
+const PARALLELISM: usize = 4;
+
+fn main() {
+    let t = Instant::now();
+    let sequential = find_nth_prime(N);
+    let sequential_time = t.elapsed();
+
+    let rt = Builder::new().core_threads(1).blocking_threads(PARALLELISM).build().expect("Couldn't build the runtime");
+    let t = Instant::now();
+    let search = async { spawn_with_handle(find_nth_prime_concurrent_candidates(N, PARALLELISM)).await };
+    let concurrent = rt.block_on(search);
+    let concurrent_time = t.elapsed();
+
+    assert_eq!(sequential, concurrent);
+    println!("The {}th prime is {}", N, sequential);
+    println!("Sequential:             {:8.3}s", sequential_time.as_secs_f64());
+    println!(
+        "{} concurrent searches:  {:8.3}s ({:.2}x faster)",
+        PARALLELISM,
+        concurrent_time.as_secs_f64(),
+        sequential_time.as_secs_f64() / concurrent_time.as_secs_f64()
+    );
+}
@@ -0,0 +1,46 @@
+//! How long the first job on a fresh runtime waits for `blocking` to start it, vs. the first job
+//! on a [PrewarmedBlockingPool]. `blocking` runs the job on the thread that calls it, so don't
+//! expect the pool to win; see the `prewarmed` module docs.
+//!
+//! Run with `cargo bench --bench prewarmed_pool`.
+
+use async_await::prewarmed::PrewarmedBlockingPool;
+use async_await::spawn_with_handle;
+use futures::executor::block_on;
+use futures::future::poll_fn;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Builder;
+use tokio_executor::threadpool::blocking;
+
+const ROUNDS: u32 = 100;
+
+/// From handing the job over to it starting, on a runtime that's never run a blocking job before.
+fn first_blocking_latency() -> Duration {
+    let rt = Builder::new().core_threads(1).blocking_threads(4).build().expect("Couldn't build the runtime");
+    let latency = rt.block_on(async {
+        spawn_with_handle(async {
+            let submitted = Instant::now();
+            poll_fn(|_| blocking(|| submitted.elapsed())).await.expect("Couldn't block")
+        })
+        .await
+    });
+    rt.shutdown_on_idle();
+    latency
+}
+
+/// The same, on a pool whose threads have had a moment to start up.
+fn first_prewarmed_latency() -> Duration {
+    let pool = PrewarmedBlockingPool::new(4);
+    thread::sleep(Duration::from_millis(1));
+    let submitted = Instant::now();
+    block_on(pool.submit(move || submitted.elapsed()))
+}
+
+fn main() {
+    let blocking_total = (0..ROUNDS).map(|_| first_blocking_latency()).sum::<Duration>();
+    let prewarmed_total = (0..ROUNDS).map(|_| first_prewarmed_latency()).sum::<Duration>();
+    println!("Time for the first job to start, averaged over {} fresh pools of 4 threads:", ROUNDS);
+    println!("blocking:           {:8.1}µs", (blocking_total / ROUNDS).as_secs_f64() * 1e6);
+    println!("Prewarmed pool:     {:8.1}µs", (prewarmed_total / ROUNDS).as_secs_f64() * 1e6);
+}
@@ -0,0 +1,47 @@
+//! How many primality tests does a shared cache save when factorizing lots of numbers?
+//!
+//! Run with `cargo bench --bench primality_cache`.
+
+use async_await::cache::PrimalityCache;
+use async_await::number_theory::{prime_factorization, prime_factorization_using};
+use std::time::Instant;
+
+/// 1000 numbers below 10^9, from a simple linear congruential generator so every run is the same.
+fn numbers() -> Vec<u64> {
+    let mut x = 12345u64;
+    (0..1000)
+        .map(|_| {
+            x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (x >> 33) % 1_000_000_000
+        })
+        .collect()
+}
+
+fn main() {
+    let numbers = numbers();
+
+    let mut uncached_calls = 0u64;
+    let t = Instant::now();
+    let uncached = numbers
+        .iter()
+        .map(|&n| {
+            prime_factorization_using(n, |d| {
+                uncached_calls += 1;
+                async_await::is_prime(d)
+            })
+        })
+        .collect::<Vec<_>>();
+    let uncached_time = t.elapsed();
+
+    let cache = PrimalityCache::new();
+    let t = Instant::now();
+    let cached = numbers.iter().map(|&n| prime_factorization(n, Some(&cache))).collect::<Vec<_>>();
+    let cached_time = t.elapsed();
+
+    assert_eq!(uncached, cached);
+    let saved = 100.0 * (1.0 - cache.computed() as f64 / uncached_calls as f64);
+    println!("Factorizing {} numbers below 10^9:", numbers.len());
+    println!("Without cache: {:9} is_prime calls, {:8.3}s", uncached_calls, uncached_time.as_secs_f64());
+    println!("With cache:    {:9} is_prime calls, {:8.3}s", cache.computed(), cached_time.as_secs_f64());
+    println!("The cache saved {:.1}% of the calls", saved);
+}
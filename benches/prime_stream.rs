@@ -0,0 +1,40 @@
+//! How much does batching primes up save over sending them through the channel one at a time?
+//!
+//! Run with `cargo bench --bench prime_stream`.
+
+use async_await::stream::{prime_stream_chunked, prime_stream_chunked_sieve, AsyncPrimeIterator};
+use futures::stream::{self, StreamExt};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Builder, Runtime};
+
+const COUNT: usize = 100_000;
+const START: u64 = 1_000_000;
+
+fn time_stream<S: stream::Stream<Item = Vec<u64>> + Send + 'static>(rt: &Runtime, make: fn() -> S) -> Duration {
+    rt.block_on(async move {
+        let t = Instant::now();
+        let primes = make().map(stream::iter).flatten().take(COUNT as u64).collect::<Vec<_>>().await;
+        assert_eq!(primes.len(), COUNT);
+        t.elapsed()
+    })
+}
+
+fn main() {
+    let rt = Builder::new().core_threads(1).blocking_threads(1).build().expect("Couldn't build the runtime");
+    let one_at_a_time = time_stream(&rt, || AsyncPrimeIterator::starting_from(START).map(|p| vec![p]));
+    let chunked = time_stream(&rt, || prime_stream_chunked(START, 1_000));
+    let sieved = time_stream(&rt, || prime_stream_chunked_sieve(START, 16_384));
+    let per_prime = |d: Duration| d.as_nanos() as f64 / COUNT as f64;
+    let row = |name: &str, d: Duration| {
+        println!("{:<20} {:8.3}s ({:7.0}ns per prime)", name, d.as_secs_f64(), per_prime(d));
+    };
+    println!("{} primes from {}:", COUNT, START);
+    row("One at a time:", one_at_a_time);
+    row("Chunks of 1000:", chunked);
+    row("Sieved 16384 chunks:", sieved);
+    println!(
+        "Batching saves {:.0}ns per prime, and sieving saves {:.0}ns more",
+        per_prime(one_at_a_time) - per_prime(chunked),
+        per_prime(chunked) - per_prime(sieved)
+    );
+}
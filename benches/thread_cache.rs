@@ -0,0 +1,33 @@
+//! How much does the thread cache help when one thread runs several searches for nearby primes?
+//!
+//! Run with `cargo bench --bench thread_cache`.
+
+use async_await::cache::{clear_thread_cache, thread_cache_stats};
+use async_await::find_nth_prime;
+use std::time::{Duration, Instant};
+
+/// Searches for `n` values that are all within 10% of each other.
+fn nearby_searches() -> Vec<u64> {
+    (0..10).map(|i| 100_000 + 1_000 * i).collect()
+}
+
+fn time_searches(clear_between: bool) -> Duration {
+    clear_thread_cache();
+    let t = Instant::now();
+    for n in nearby_searches() {
+        if clear_between {
+            clear_thread_cache();
+        }
+        find_nth_prime(n);
+    }
+    t.elapsed()
+}
+
+fn main() {
+    let cold = time_searches(true);
+    let warm = time_searches(false);
+    let (hits, misses) = thread_cache_stats();
+    println!("Without cache: {:8.3}s", cold.as_secs_f64());
+    println!("With cache:    {:8.3}s ({:.1}x faster)", warm.as_secs_f64(), cold.as_secs_f64() / warm.as_secs_f64());
+    println!("Cache hits: {}, misses: {}", hits, misses);
+}
@@ -0,0 +1,55 @@
+//! Pre-computes the prime lookup tables at compile time, so they cost nothing at runtime.
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+// Share the sieve with the crate itself, rather than keeping a second copy here.
+#[allow(dead_code)]
+#[path = "src/sieve.rs"]
+mod sieve;
+
+use sieve::sieve_primes;
+
+/// One bit for every number below 65536, set if the number is prime.
+fn write_small_primes_bitset(out_dir: &Path) {
+    let mut words = [0u64; 1024];
+    for p in sieve_primes(65535) {
+        words[p as usize / 64] |= 1 << (p % 64);
+    }
+    // There are 6542 primes below 65536. If the sieve ever gets this wrong, fail the build rather
+    // than ship a broken table.
+    let count: u32 = words.iter().map(|w| w.count_ones()).sum();
+    assert_eq!(count, 6542, "SMALL_PRIMES_BITSET has the wrong number of primes");
+
+    let mut f = fs::File::create(out_dir.join("small_primes_bitset.rs")).unwrap();
+    writeln!(f, "pub static SMALL_PRIMES_BITSET: [u64; 1024] = [").unwrap();
+    for w in words.iter() {
+        writeln!(f, "    {:#018x},", w).unwrap();
+    }
+    writeln!(f, "];").unwrap();
+}
+
+/// The first 10000 primes. The 10000th prime is 104729, so sieving to 105000 is plenty.
+fn write_first_10000_primes(out_dir: &Path) {
+    let primes = &sieve_primes(105_000)[..10_000];
+    assert_eq!(primes[9999], 104_729, "FIRST_10000_PRIMES doesn't end with the 10000th prime");
+
+    let mut f = fs::File::create(out_dir.join("first_10000_primes.rs")).unwrap();
+    writeln!(f, "pub static FIRST_10000_PRIMES: [u32; 10000] = [").unwrap();
+    for row in primes.chunks(10) {
+        let row = row.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        writeln!(f, "    {},", row.join(", ")).unwrap();
+    }
+    writeln!(f, "];").unwrap();
+}
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let out_dir = Path::new(&out_dir);
+    write_small_primes_bitset(out_dir);
+    write_first_10000_primes(out_dir);
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/sieve.rs");
+}
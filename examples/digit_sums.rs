@@ -0,0 +1,13 @@
+//! The digit sums of a few big primes, each one searched for on a blocking thread and summed back
+//! on the async side. Run with `cargo run --release --example digit_sums`.
+
+use async_await::digits::sum_digits_parallel;
+
+#[tokio::main]
+async fn main() {
+    let ns = vec![10, 1_000, 100_000, 1_000_000, 2_000_000];
+    let sums = sum_digits_parallel(ns.clone()).await;
+    for (n, sum) in ns.iter().zip(sums) {
+        println!("The digits of the {}th prime add up to {}", n, sum);
+    }
+}
@@ -0,0 +1,37 @@
+//! Fetching a web page while a long prime search runs on a blocking thread.
+//!
+//! Pass a `http://` URL to fetch it, or leave it off and we'll start a little server on localhost
+//! that takes a second to respond. Either way the response turns up long before the search is done.
+//! Run with `cargo run --release --example interleaved_io [url]`.
+
+use async_await::interleaved::interleaved_io_and_compute;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+/// Serve a single request on a std thread, after a short delay, and return its URL.
+fn slow_local_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Couldn't start the local server");
+    let url = format!("http://{}/", listener.local_addr().expect("The local server has no address"));
+    thread::spawn(move || {
+        if let Ok((mut stream, _)) = listener.accept() {
+            // Read the request up to the blank line that ends the headers
+            let mut reader = BufReader::new(stream.try_clone().expect("Couldn't clone the connection"));
+            let mut line = String::new();
+            while reader.read_line(&mut line).map(|read| read > 0).unwrap_or(false) && line != "\r\n" {
+                line.clear();
+            }
+            thread::sleep(Duration::from_secs(1));
+            stream.write_all(b"HTTP/1.0 200 OK\r\nContent-Length: 5\r\n\r\nhello").ok();
+        }
+    });
+    url
+}
+
+#[tokio::main]
+async fn main() {
+    let url = std::env::args().nth(1).unwrap_or_else(slow_local_server);
+    let (result, body) = interleaved_io_and_compute(1_000_000, &url).await;
+    println!("Found {} and got {} bytes back", result.value, body.map(|b| b.len()).unwrap_or(0));
+}
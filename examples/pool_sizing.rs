@@ -0,0 +1,44 @@
+//! Letting a [DynamicThreadPoolSizer] pick the blocking thread count, one runtime at a time.
+//!
+//! Each round builds a fresh runtime with the recommended number of blocking threads, runs a
+//! batch of searches on it, and tells the sizer how busy the threads were. The queue starts out
+//! deep, so the thread count climbs until the backlog is gone. Run with
+//! `cargo run --release --example pool_sizing`.
+//!
+//! How busy the threads were is measured on the wall clock. With more threads than cores, the
+//! threads take turns on the cores, so the extra threads look busy without getting any more done.
+//! Keep an eye on how long each batch takes, not just on the recommendation.
+
+use async_await::pool_sizing::DynamicThreadPoolSizer;
+use async_await::spawn_prime_output;
+use futures::executor::block_on;
+use futures::future::join_all;
+use std::time::{Duration, Instant};
+
+fn main() {
+    let mut sizer = DynamicThreadPoolSizer::new(1, 8);
+    let mut queue_depth = 40u64;
+    let batch_size = 8;
+    while queue_depth > 0 {
+        let rt = sizer.build_runtime_for();
+        let batch = queue_depth.min(batch_size);
+        let t = Instant::now();
+        let results = rt.block_on(async move {
+            join_all((0..batch).map(|i| spawn_prime_output(i, 50_000 + 1_000 * i))).await
+        });
+        let wall = t.elapsed();
+        queue_depth -= batch;
+        let busy = results.iter().map(|r| r.elapsed).sum::<Duration>();
+        let completed_rate = busy.as_secs_f64() / wall.as_secs_f64();
+        println!(
+            "{} threads: {} searches in {:.3}s, {:.2} threads' worth of work, {} still queued",
+            sizer.current,
+            batch,
+            wall.as_secs_f64(),
+            completed_rate,
+            queue_depth
+        );
+        block_on(sizer.adjust(queue_depth, completed_rate));
+    }
+    println!("Finished with a recommendation of {} threads", sizer.current);
+}
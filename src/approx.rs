@@ -0,0 +1,212 @@
+//! Quick estimates of how big the `n`th prime is.
+//!
+//! Finding the `n`th prime exactly is the slow part of the demo, but plenty of things only need a
+//! rough idea of the answer: how long a search is likely to take, or how to split a range of
+//! numbers up into pieces that hold about the same number of primes.
+
+use crate::parallel_search::find_nth_prime_parallel_sieve;
+use crate::sieve::first_primes;
+use crate::tables::{kth_prime_table, FIRST_10000_PRIMES};
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+/// Something that can estimate the `n`th prime (counting from 1).
+pub trait PrimeApproximator {
+    fn nth_prime_approx(&self, n: u64) -> u64;
+}
+
+/// The logs in the formulae below don't behave for tiny `n`, so we just look those primes up.
+fn tiny_nth_prime(n: u64) -> Option<u64> {
+    match n {
+        0 => Some(0),
+        1..=5 => kth_prime_table(n),
+        _ => None,
+    }
+}
+
+/// `n (ln n + ln ln n)`, straight from the prime number theorem.
+///
+/// This is an upper bound for every `n` from 6 on, and a loose one: about 12% too high around
+/// `n = 1000`, shrinking slowly to about 6% by `n = 1,000,000`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrimeNumberTheoremApprox;
+
+impl PrimeApproximator for PrimeNumberTheoremApprox {
+    fn nth_prime_approx(&self, n: u64) -> u64 {
+        tiny_nth_prime(n).unwrap_or_else(|| {
+            let n = n as f64;
+            (n * (n.ln() + n.ln().ln())) as u64
+        })
+    }
+}
+
+/// Cipolla's refinement of the estimate that Rosser bounded:
+/// `n (ln n + ln ln n - 1 + (ln ln n - 2) / ln n)`.
+///
+/// The extra terms pull the estimate to within about 1% of the `n`th prime for `n = 1000`, and
+/// within 0.02% by `n = 100,000`. It is poor for very small `n`, though: it guesses 3 for the 6th
+/// prime.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RosserApprox;
+
+impl PrimeApproximator for RosserApprox {
+    fn nth_prime_approx(&self, n: u64) -> u64 {
+        tiny_nth_prime(n).unwrap_or_else(|| {
+            let n = n as f64;
+            let (ln, lnln) = (n.ln(), n.ln().ln());
+            (n * (ln + lnln - 1.0 + (lnln - 2.0) / ln)) as u64
+        })
+    }
+}
+
+/// The `10^k`th primes, for `k` from 4 to 12.
+static POWER_OF_TEN_PRIMES: [(u64, u64); 9] = [
+    (10_000, 104_729),
+    (100_000, 1_299_709),
+    (1_000_000, 15_485_863),
+    (10_000_000, 179_424_673),
+    (100_000_000, 2_038_074_743),
+    (1_000_000_000, 22_801_763_489),
+    (10_000_000_000, 252_097_800_623),
+    (100_000_000_000, 2_760_727_302_517),
+    (1_000_000_000_000, 29_996_224_275_833),
+];
+
+/// Look the answer up where we can, and interpolate where we can't.
+///
+/// The first 10000 primes come straight from [FIRST_10000_PRIMES]. Past that, we know the `n`th
+/// prime exactly at every power of ten up to `10^12`. In between, `p_n / n` is close to a straight
+/// line in `ln n`, so we interpolate along that line. Beyond `10^12` we fall back to [RosserApprox].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogTableApprox;
+
+impl PrimeApproximator for LogTableApprox {
+    fn nth_prime_approx(&self, n: u64) -> u64 {
+        if let Some(p) = tiny_nth_prime(n).or_else(|| kth_prime_table(n)) {
+            return p;
+        }
+        let upper = match POWER_OF_TEN_PRIMES.iter().position(|&(k, _)| k >= n) {
+            Some(i) => i,
+            None => return RosserApprox.nth_prime_approx(n),
+        };
+        let (k1, p1) = POWER_OF_TEN_PRIMES[upper];
+        let (k0, p0) = match upper {
+            0 => (FIRST_10000_PRIMES.len() as u64, *FIRST_10000_PRIMES.last().unwrap() as u64),
+            _ => POWER_OF_TEN_PRIMES[upper - 1],
+        };
+        let ratio = |k: u64, p: u64| p as f64 / k as f64;
+        let (x, x0, x1) = ((n as f64).ln(), (k0 as f64).ln(), (k1 as f64).ln());
+        let t = if x1 > x0 { (x - x0) / (x1 - x0) } else { 1.0 };
+        let r = ratio(k0, p0) + t * (ratio(k1, p1) - ratio(k0, p0));
+        (n as f64 * r) as u64
+    }
+}
+
+/// The most accurate approximator we have, which is [LogTableApprox].
+pub fn best_approximator() -> Box<dyn PrimeApproximator> {
+    Box::new(LogTableApprox)
+}
+
+/// The most an estimate from [best_approximator] may be off by, in percent, before
+/// [verify_approximation_quality] complains.
+pub const MAX_APPROXIMATION_ERROR_PERCENT: f64 = 5.0;
+
+/// The relative error of `approx`, in percent. Positive if it's an overestimate.
+fn error_percent(approx: u64, exact: u64) -> f64 {
+    (approx as f64 - exact as f64) / exact as f64 * 100.0
+}
+
+/// An estimate that was further off than [MAX_APPROXIMATION_ERROR_PERCENT].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApproximationError {
+    pub n: u64,
+    pub approx: u64,
+    /// The actual `n`th prime
+    pub exact: u64,
+    pub error_percent: f64,
+}
+
+impl fmt::Display for ApproximationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "estimated the {}th prime as {}, but it's {} ({:+.2}%)",
+            self.n, self.approx, self.exact, self.error_percent
+        )
+    }
+}
+
+impl Error for ApproximationError {}
+
+/// Estimate the `n`th prime with [best_approximator], and find it exactly with
+/// [find_nth_prime_parallel_sieve]. Returns `(estimate, exact, error in percent)`, where the error
+/// is positive if the estimate was too high.
+pub async fn prime_nth_approx_and_verify(n: u64) -> (u64, u64, f64) {
+    let approx = best_approximator().nth_prime_approx(n);
+    let exact = find_nth_prime_parallel_sieve(n).await;
+    (approx, exact, error_percent(approx, exact))
+}
+
+/// Check that [best_approximator] is within [MAX_APPROXIMATION_ERROR_PERCENT] of the `n`th prime
+/// for every `n` in `n_range`, and report the first `n` where it isn't. The whole range is checked
+/// against a single sieve, so it's fine to check thousands of values at once.
+pub fn verify_approximation_quality(n_range: Range<u64>) -> Result<(), ApproximationError> {
+    verify_approximator(&*best_approximator(), n_range)
+}
+
+/// [verify_approximation_quality], for any `approximator`.
+fn verify_approximator(approximator: &dyn PrimeApproximator, n_range: Range<u64>) -> Result<(), ApproximationError> {
+    let primes = first_primes(n_range.end as usize);
+    for n in n_range.filter(|&n| n > 0) {
+        let (approx, exact) = (approximator.nth_prime_approx(n), primes[n as usize - 1]);
+        let error_percent = error_percent(approx, exact);
+        if error_percent.abs() > MAX_APPROXIMATION_ERROR_PERCENT {
+            return Err(ApproximationError { n, approx, exact, error_percent });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approximations_are_close() {
+        let approximators: [&dyn PrimeApproximator; 3] = [&PrimeNumberTheoremApprox, &RosserApprox, &LogTableApprox];
+        let exact = first_primes(1_000_000);
+        for &n in &[1000, 10_000, 100_000, 1_000_000] {
+            for (i, approximator) in approximators.iter().enumerate() {
+                let error = error_percent(approximator.nth_prime_approx(n), exact[n as usize - 1]);
+                // The prime number theorem's estimate is about 12% high at 1000, as its docs say
+                let limit = if i == 0 && n == 1000 { 12.0 } else { 10.0 };
+                assert!(error.abs() < limit, "approximator {} is {:.1}% out for n = {}", i, error, n);
+            }
+        }
+    }
+
+    #[test]
+    fn estimates_stay_within_the_limit() {
+        // Up to 10,000, the best approximator just looks the answer up, so we check past that
+        crate::prime_test! {
+            for &(n, nth_prime) in &[(10_001, 104_743), (31_623, 371_341), (99_999, 1_299_689)] {
+                let (approx, exact, error) = prime_nth_approx_and_verify(n).await;
+                assert_eq!(exact, nth_prime);
+                assert_ne!(approx, exact);
+                assert_eq!(error, error_percent(approx, exact));
+                assert!(error.abs() < MAX_APPROXIMATION_ERROR_PERCENT, "{}% out for n = {}", error, n);
+            }
+        }
+        assert_eq!(verify_approximation_quality(10_001..100_001), Ok(()));
+    }
+
+    #[test]
+    fn loose_approximators_fail_the_check() {
+        assert_eq!(verify_approximator(&RosserApprox, 10_001..100_001), Ok(()));
+        // The prime number theorem's estimate is about 10% high here
+        let error = verify_approximator(&PrimeNumberTheoremApprox, 10_001..100_001).unwrap_err();
+        assert_eq!((error.n, error.exact), (10_001, 104_743));
+        assert!(error.error_percent > MAX_APPROXIMATION_ERROR_PERCENT);
+    }
+}
@@ -0,0 +1,16 @@
+//! The demo's 20 prime searches, with as little code as possible.
+//!
+//! [prime_output_batch] does all the spawning and blocking for us, and keeps 5 searches running
+//! at a time, just like the 5 blocking threads in the main demo. The catch is that we don't see
+//! anything until the last search is done, and then the results come back in id order, so there's
+//! no telling what order they finished in.
+
+use async_await::{prime_output_batch, DemoConfig};
+
+#[tokio::main]
+async fn main() {
+    for result in prime_output_batch(DemoConfig::default().tasks(), 5).await {
+        println!("{}", result);
+    }
+    println!("Bye");
+}
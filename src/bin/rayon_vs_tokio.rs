@@ -0,0 +1,107 @@
+//! Rayon vs. Tokio's blocking threads, on the same CPU-bound workload.
+//!
+//! We find the 20 millionth prime, the 19 millionth, and so on down to the millionth, twice over:
+//! once in a `rayon::scope` on a 5 thread rayon pool, and once on a Tokio runtime with 5 blocking
+//! threads. Run with `cargo run --release --bin rayon_vs_tokio`.
+//!
+//! Trial division, as in `find_nth_prime`, would take days to get that far, so both sides test
+//! each candidate with `is_prime_fast` instead. Each search still runs on a single thread from
+//! start to finish, with no memory to speak of, so the two pools are doing exactly the same work.
+//!
+//! For pure number crunching like this, the two get through the work at about the same rate. The
+//! search is the bottleneck, not the scheduling. Here's a run on a single core machine, where the 5
+//! threads on each side take turns on the one core, and Tokio finishes about 10% behind:
+//! ```text
+//!  #        n  rayon (s)  tokio (s)
+//!  0 20000000    383.811    476.938
+//!  1 19000000    363.146    449.620
+//!  2 18000000    343.145    423.055
+//!  3 17000000    322.826    397.797
+//!  4 16000000    352.972    373.702
+//!  5 15000000    333.208    345.248
+//!  6 14000000    313.264    321.339
+//!  7 13000000    293.153    296.466
+//!  8 12000000    186.794    270.383
+//!  9 11000000    181.329    243.769
+//! 10 10000000    171.070    212.709
+//! 11  9000000    155.947    189.312
+//! 12  8000000    184.839    166.809
+//! 13  7000000    137.073    143.657
+//! 14  6000000    106.389    120.382
+//! 15  5000000     91.201    104.560
+//! 16  4000000     65.521     81.734
+//! 17  3000000     50.646     59.544
+//! 18  2000000     32.191     37.876
+//! 19  1000000     15.054     15.171
+//!  wall clock    862.620    946.866
+//! ```
+//!
+//! The difference is in how they fit into the rest of your program:
+//!
+//! * Rayon is the simpler API for CPU work, but the thread that starts the work blocks until it's
+//!   all done: `scope` doesn't return until every search spawned in it has finished.
+//! * With Tokio, the searches are just futures. The runtime keeps on serving async I/O and other
+//!   tasks while the blocking threads grind away, and results can be used as soon as they arrive.
+
+use async_await::primality::PrimeIterator;
+use async_await::spawn_with_handle;
+use futures::future::{join_all, poll_fn};
+use std::time::{Duration, Instant};
+use tokio_executor::threadpool::blocking;
+
+const THREADS: usize = 5;
+
+/// The `(id, n)` searches, from the 20 millionth prime down to the millionth.
+fn tasks() -> Vec<(u64, u64)> {
+    (0..20).map(|i| (i, 20_000_000 - 1_000_000 * i)).collect()
+}
+
+/// Find the `n`th prime by testing every number in turn, and say how long it took.
+fn timed_search(n: u64) -> Duration {
+    let t = Instant::now();
+    PrimeIterator::starting_from(2).nth(n as usize - 1).expect("Ran out of primes");
+    t.elapsed()
+}
+
+fn with_rayon() -> (Vec<Duration>, Duration) {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(THREADS).build().expect("Could not create rayon pool");
+    let mut elapsed = vec![Duration::ZERO; tasks().len()];
+    let t = Instant::now();
+    // `scope` lets the searches borrow their slots in `elapsed`, since it waits for all of them
+    pool.scope(|s| {
+        for (slot, (_, n)) in elapsed.iter_mut().zip(tasks()) {
+            s.spawn(move |_| *slot = timed_search(n));
+        }
+    });
+    (elapsed, t.elapsed())
+}
+
+fn with_tokio() -> (Vec<Duration>, Duration) {
+    let rt = tokio::runtime::Builder::new()
+        .blocking_threads(THREADS)
+        .core_threads(1)
+        .build()
+        .expect("Could not create runtime");
+    let t = Instant::now();
+    let elapsed = rt.block_on(async {
+        let searches = tasks().into_iter().map(|(_, n)| {
+            spawn_with_handle(async move {
+                poll_fn(|_| blocking(|| timed_search(n))).await.expect("Couldn't block")
+            })
+        });
+        join_all(searches.collect::<Vec<_>>()).await
+    });
+    let wall = t.elapsed();
+    rt.shutdown_on_idle();
+    (elapsed, wall)
+}
+
+fn main() {
+    let (rayon_times, rayon_wall) = with_rayon();
+    let (tokio_times, tokio_wall) = with_tokio();
+    println!("{:>2} {:>8} {:>10} {:>10}", "#", "n", "rayon (s)", "tokio (s)");
+    for (((id, n), r), t) in tasks().into_iter().zip(rayon_times).zip(tokio_times) {
+        println!("{:2} {:8} {:10.3} {:10.3}", id, n, r.as_secs_f64(), t.as_secs_f64());
+    }
+    println!("{:>11} {:10.3} {:10.3}", "wall clock", rayon_wall.as_secs_f64(), tokio_wall.as_secs_f64());
+}
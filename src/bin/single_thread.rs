@@ -0,0 +1,104 @@
+//! The same 20 prime searches as the main demo, but on a `current_thread` runtime.
+//!
+//! A `current_thread` runtime polls every future on the one thread that called `block_on`. That's
+//! handy when resources are tight: there are no extra scheduler threads sitting around, nothing to
+//! synchronise between them, and `!Send` futures are fine. The catch is that it has no blocking
+//! pool, so `blocking` doesn't work at all (the first thing this demo does is show that).
+//!
+//! Instead, we hand the searches off to a small pool of plain OS threads, and send each result
+//! back on a oneshot channel. That's all `blocking` really does for us in the thread pool runtime.
+//! The async side only ever waits on the channels, so the one runtime thread stays free to run
+//! other tasks while the searches grind away.
+//!
+//! The searches are the bottleneck, and the scheduler thread spends nearly all its time idle
+//! either way, so with the same 5 search threads this takes about as long as the thread pool
+//! version. Here's a run on a single core machine, built with `--release`:
+//! ```text
+//! As expected, there's no blocking pool here: `blocking` annotation used from outside the context of a thread pool
+//! # 4, 4200000th prime =     71480051 ( 5m 10s)
+//! # 3, 4400000th prime =     75103493 ( 5m 33s)
+//! # 2, 4600000th prime =     78736451 ( 5m 55s)
+//! # 1, 4800000th prime =     82376219 ( 6m 18s)
+//! # 0, 5000000th prime =     86028121 ( 6m 43s)
+//!
+//! # 5, 4000000th prime =     67867967 (  5m 4s)
+//! # 6, 3800000th prime =     64268779 ( 4m 42s)
+//! # 7, 3600000th prime =     60678089 ( 4m 21s)
+//! # 8, 3400000th prime =     57099299 (  4m 2s)
+//! # 9, 3200000th prime =     53533511 ( 3m 41s)
+//!
+//! #14, 2200000th prime =     35926307 (  2m 1s)
+//! #13, 2400000th prime =     39410867 ( 2m 18s)
+//! #12, 2600000th prime =     42920191 ( 2m 37s)
+//! #11, 2800000th prime =     46441207 ( 2m 56s)
+//! #10, 3000000th prime =     49979687 ( 3m 16s)
+//!
+//! #17, 1600000th prime =     25582153 ( 1m 20s)
+//! #16, 1800000th prime =     29005541 ( 1m 36s)
+//! #18, 1400000th prime =     22182343 (  1m 4s)
+//! #15, 2000000th prime =     32452843 ( 1m 50s)
+//! #19, 1200000th prime =     18815231 ( 47.08s)
+//! Bye
+//! ```
+//!
+//! That's 14m 18s from start to finish. The same 20 searches with `try_prime_output` on the thread
+//! pool runtime from `main.rs`, 5 blocking threads and 1 core thread, took 15m 20s on the same
+//! machine, finishing in the same order. The run recorded in `main.rs` was on a machine that ran
+//! its threads in parallel (3160 thread-seconds in 638s), which is why its times are shorter and
+//! its batches come back in a different order.
+
+use async_await::memory::MemoryTracker;
+use async_await::{find_nth_prime, try_prime_output, PrimeResult};
+use futures::channel::oneshot;
+use futures::future::join_all;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Start `n` threads that run jobs from the returned queue, one at a time, until it is dropped.
+fn search_threads(n: usize) -> mpsc::Sender<Job> {
+    let (tx, rx) = mpsc::channel::<Job>();
+    let rx = Arc::new(Mutex::new(rx));
+    for _ in 0..n {
+        let rx = rx.clone();
+        thread::spawn(move || loop {
+            let job = rx.lock().unwrap().recv();
+            match job {
+                Ok(job) => job(),
+                Err(_) => break,
+            }
+        });
+    }
+    tx
+}
+
+/// Queue up the search for the `n`th prime, and wait for the result.
+async fn prime_output(threads: &mpsc::Sender<Job>, id: u64, n: u64) -> PrimeResult {
+    let (tx, rx) = oneshot::channel();
+    let job = Box::new(move || {
+        let tracker = MemoryTracker::start();
+        let t = Instant::now();
+        let value = find_nth_prime(n);
+        let elapsed = t.elapsed();
+        let result = PrimeResult { id, n, value, started_at: t, elapsed, peak_memory: tracker.peak_usage() };
+        println!("{}", result);
+        tx.send(result).ok();
+    });
+    threads.send(job).expect("The search threads have stopped");
+    rx.await.expect("A search thread panicked")
+}
+
+#[tokio::main(single_thread)]
+async fn main() {
+    if let Err(e) = try_prime_output(0, 10).await {
+        println!("As expected, there's no blocking pool here: {}", e);
+    }
+    let threads = search_threads(5);
+    let max = 5_000_000u64;
+    let searches = (0..20).map(|i| prime_output(&threads, i, max - 200_000 * i));
+    join_all(searches).await;
+    println!("Bye");
+}
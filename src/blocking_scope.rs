@@ -0,0 +1,64 @@
+//! Running lots of small blocking jobs without paying for a blocking thread each.
+//!
+//! Handing a closure to `blocking` isn't free: the task has to claim one of the runtime's blocking
+//! slots, and the thread pool has to hand its worker thread's other tasks off while the closure
+//! runs. For a search that takes seconds, that's nothing. For a hundred primality tests that take
+//! microseconds each, it's most of the work.
+//!
+//! [blocking_scope] claims a single slot for a whole batch of closures, and spreads them across
+//! rayon's thread pool from there.
+
+use futures::future::poll_fn;
+use tokio_executor::threadpool::blocking;
+
+/// Run all of `tasks` in parallel, from a single blocking slot, and return their outputs in the
+/// same order.
+///
+/// The blocking thread hands the tasks to rayon's global thread pool with `rayon::scope`, and
+/// waits for them all to finish, so the batch takes up one of the runtime's blocking threads
+/// however many tasks are in it. As with anything that uses `blocking`, this has to be `spawn`ed
+/// onto the runtime.
+pub async fn blocking_scope<F, T>(tasks: Vec<F>) -> Vec<T>
+where
+    F: FnOnce() -> T + Send,
+    T: Send,
+{
+    // `poll_fn` may call this more than once if there's no blocking slot free, but `blocking` only
+    // runs the closure once it has a slot, so the tasks are only taken once
+    let mut tasks = Some(tasks);
+    poll_fn(move |_| {
+        blocking(|| {
+            let tasks = tasks.take().expect("The tasks have already run");
+            let mut outputs = tasks.iter().map(|_| None).collect::<Vec<_>>();
+            rayon::scope(|s| {
+                for (task, output) in tasks.into_iter().zip(outputs.iter_mut()) {
+                    s.spawn(move |_| *output = Some(task()));
+                }
+            });
+            outputs.into_iter().map(|output| output.expect("A task didn't run")).collect()
+        })
+    }).await.expect("Couldn't block")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn_with_handle;
+    use crate::testing::test_runtime_single_thread;
+
+    #[test]
+    fn ten_primality_tests_come_back_in_order() {
+        let candidates = [2, 4, 97, 561, 7919, 7921, 104_729, 1_000_000, 999_983, 2_147_483_647];
+        let tasks = candidates.iter().map(|&n| move || (n, crate::is_prime(n))).collect::<Vec<_>>();
+        // The whole batch only needs the one blocking thread
+        let results = test_runtime_single_thread().block_on(async { spawn_with_handle(blocking_scope(tasks)).await });
+        let expected = [true, false, true, false, true, false, true, false, true, true];
+        assert_eq!(results, candidates.iter().copied().zip(expected.iter().copied()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn empty_batches_are_fine() {
+        let tasks: Vec<fn() -> u64> = Vec::new();
+        assert!(crate::prime_test! { spawn_with_handle(blocking_scope(tasks)).await }.is_empty());
+    }
+}
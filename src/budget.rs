@@ -0,0 +1,134 @@
+//! Running a batch of searches on a fixed CPU budget.
+//!
+//! We only run the searches on blocking threads, and each search has a thread to itself, so the
+//! wall-clock time spent inside the blocking closures is a decent stand-in for CPU time. Each
+//! search charges the time it has used to a shared [Budget] every time it checks for cancellation.
+//! Once the budget is spent, the batch's token is cancelled, which stops the running searches at
+//! their next check and stops the rest from starting at all.
+
+use crate::cancellation::{shutdown_token, CancellationToken};
+use crate::events::{event_bus, Event};
+use crate::{find_nth_prime_interruptible, try_time_search, PrimeResult};
+use futures::future::{poll_fn, ready, FutureExt};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_executor::threadpool::blocking;
+
+/// CPU time shared between the searches of a batch.
+pub struct Budget {
+    limit: Duration,
+    spent_nanos: AtomicU64,
+    /// Set by the first charge that goes over the limit
+    exhausted: AtomicBool,
+    token: CancellationToken,
+}
+
+impl Budget {
+    /// A budget of `limit`, which cancels `token` once it's used up.
+    pub fn new(limit: Duration, token: CancellationToken) -> Self {
+        Budget { limit, spent_nanos: AtomicU64::new(0), exhausted: AtomicBool::new(false), token }
+    }
+
+    /// Charge `time` to the budget. Returns whether there is any budget left.
+    pub fn charge(&self, time: Duration) -> bool {
+        let spent = self.spent_nanos.fetch_add(time.as_nanos() as u64, Ordering::SeqCst) + time.as_nanos() as u64;
+        let spent = Duration::from_nanos(spent);
+        if spent <= self.limit {
+            return true;
+        }
+        // Only the search that tips us over the limit publishes the event
+        if !self.exhausted.swap(true, Ordering::SeqCst) {
+            self.token.cancel();
+            event_bus().publish(Event::BudgetExhausted { budget: self.limit, spent });
+        }
+        false
+    }
+
+    pub fn spent(&self) -> Duration {
+        Duration::from_nanos(self.spent_nanos.load(Ordering::SeqCst))
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+async fn budgeted_prime_output(id: u64, n: u64, budget: Arc<Budget>) -> Option<PrimeResult> {
+    poll_fn(|_| {
+        blocking(|| {
+            if budget.is_exhausted() {
+                return None;
+            }
+            let mut last_charged = Instant::now();
+            let mut charge = || {
+                let now = Instant::now();
+                let within_budget = budget.charge(now - last_charged);
+                last_charged = now;
+                within_budget
+            };
+            try_time_search(id, n, |n| {
+                let value = find_nth_prime_interruptible(n, || !charge())?;
+                charge();
+                Some(value)
+            })
+        })
+    }).await.expect("Couldn't block")
+}
+
+/// Run the `(id, n)` searches until they've used up `cpu_budget` between them, and return the
+/// results of the searches that finished in time, in the order they finished.
+///
+/// Each search is the slow one from [crate::try_prime_output], not the sieves [crate::prime_output]
+/// uses for big `n`, because it's the one that checks in often enough to be stopped.
+pub async fn find_primes_with_budget(tasks: Vec<(u64, u64)>, cpu_budget: Duration) -> Vec<PrimeResult> {
+    let budget = Arc::new(Budget::new(cpu_budget, shutdown_token().child_token()));
+    let searches = tasks.into_iter().map(|(id, n)| {
+        let (task, handle) = budgeted_prime_output(id, n, budget.clone()).remote_handle();
+        tokio::spawn(task);
+        handle
+    }).collect::<FuturesUnordered<_>>();
+    searches.filter_map(ready).collect().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_runtime_single_thread;
+    use std::thread;
+
+    #[test]
+    fn stops_once_the_budget_is_spent() {
+        // One thread, so that the searches run one at a time, and the quick one goes first without
+        // sharing the budget
+        let rt = test_runtime_single_thread();
+        // One search that's over in no time, and nine that would take minutes
+        let tasks = (0..10).map(|id| (id, if id == 0 { 10_500 } else { 10_000_000 })).collect();
+        let started = Instant::now();
+        let results = rt.block_on(find_primes_with_budget(tasks, Duration::from_millis(100)));
+        assert!(started.elapsed() < Duration::from_millis(500), "took {:?}", started.elapsed());
+        assert_eq!(results.iter().map(|r| (r.id, r.value)).collect::<Vec<_>>(), vec![(0, 110_597)]);
+    }
+
+    #[test]
+    fn only_the_first_overspend_publishes() {
+        let mut events = event_bus().subscribe();
+        let budget = Arc::new(Budget::new(Duration::from_millis(1), CancellationToken::new()));
+        let chargers = (0..8).map(|_| {
+            let budget = budget.clone();
+            thread::spawn(move || budget.charge(Duration::from_millis(1)))
+        }).collect::<Vec<_>>();
+        let within = chargers.into_iter().map(|c| c.join().unwrap()).filter(|&ok| ok).count();
+        assert_eq!(within, 1);
+        assert!(budget.is_exhausted());
+        // Other tests publish on the same bus, so only count this budget's events
+        let mut exhausted = 0;
+        while let Ok(Some(Event::BudgetExhausted { budget, .. })) = events.try_next() {
+            if budget == Duration::from_millis(1) {
+                exhausted += 1;
+            }
+        }
+        assert_eq!(exhausted, 1);
+    }
+}
@@ -0,0 +1,169 @@
+//! Caches of primes we've already found.
+//!
+//! Every search needs to find all the primes below its target, so once a blocking thread has found
+//! the `k`th prime, a later search for the `n`th prime (`n >= k`) on the same thread can pick up
+//! from there instead of starting again from 2. Keeping the cache thread local means the blocking
+//! threads never have to wait on each other for it.
+//!
+//! [PrimalityCache] is different: it remembers which numbers are prime, and it's shared between
+//! threads, because the numbers worth caching (small trial divisors) are the same on every thread.
+
+use dashmap::DashMap;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+thread_local! {
+    /// `n -> nth prime` for every search that has finished on this thread.
+    static THREAD_CACHE: RefCell<HashMap<u64, u64>> = RefCell::new(HashMap::new());
+}
+
+// These are shared between all threads, so they count the lookups of the whole process.
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Find the best head start this thread has for finding the `n`th prime: the largest `k <= n`
+/// that this thread has already searched for, along with the `k`th prime.
+pub fn thread_cache_lookup(n: u64) -> Option<(u64, u64)> {
+    let best = THREAD_CACHE.with(|cache| {
+        cache.borrow().iter().filter(|(&k, _)| k <= n).max_by_key(|(&k, _)| k).map(|(&k, &v)| (k, v))
+    });
+    match best {
+        Some(_) => HITS.fetch_add(1, Ordering::Relaxed),
+        None => MISSES.fetch_add(1, Ordering::Relaxed),
+    };
+    best
+}
+
+/// Remember that the `n`th prime is `value` on this thread.
+pub fn thread_cache_insert(n: u64, value: u64) {
+    THREAD_CACHE.with(|cache| cache.borrow_mut().insert(n, value));
+}
+
+/// Forget everything this thread has found.
+pub fn clear_thread_cache() {
+    THREAD_CACHE.with(|cache| cache.borrow_mut().clear());
+}
+
+/// The number of `(hits, misses)` of the thread caches, across all threads.
+pub fn thread_cache_stats() -> (usize, usize) {
+    (HITS.load(Ordering::Relaxed) as usize, MISSES.load(Ordering::Relaxed) as usize)
+}
+
+/// A cache of [crate::is_prime] results that any number of threads can share.
+///
+/// It's meant for the small numbers that get tested over and over, like the candidate divisors in
+/// [crate::number_theory::prime_factorization]. Every number tested stays in the cache for good,
+/// so don't use it for one-off tests of big numbers.
+#[derive(Debug, Default)]
+pub struct PrimalityCache {
+    map: DashMap<u64, bool>,
+    computed: AtomicU64,
+}
+
+impl PrimalityCache {
+    pub fn new() -> Self {
+        PrimalityCache::default()
+    }
+
+    /// Whether `n` is prime, calling [crate::is_prime] only if nobody has asked about `n` before.
+    pub fn is_prime_cached(&self, n: u64) -> bool {
+        if let Some(prime) = self.map.get(&n) {
+            return *prime;
+        }
+        self.computed.fetch_add(1, Ordering::Relaxed);
+        let prime = crate::is_prime(n);
+        self.map.insert(n, prime);
+        prime
+    }
+
+    /// How many times the cache has had to call [crate::is_prime]. Two threads that miss on the
+    /// same number at the same time both count.
+    pub fn computed(&self) -> u64 {
+        self.computed.load(Ordering::Relaxed)
+    }
+
+    /// How many numbers are in the cache.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// A cache of `n -> nth prime` that any number of threads can share.
+///
+/// Unlike the thread caches, a search on any thread can use what a search on any other thread
+/// found, but only for exactly the same `n`: there's no head start for a bigger one.
+#[derive(Debug, Default)]
+pub struct PrimeCache {
+    map: DashMap<u64, u64>,
+}
+
+impl PrimeCache {
+    pub fn new() -> Self {
+        PrimeCache::default()
+    }
+
+    /// The `n`th prime, if a search for it has already finished.
+    pub fn get(&self, n: u64) -> Option<u64> {
+        self.map.get(&n).map(|value| *value)
+    }
+
+    /// Remember that the `n`th prime is `value`.
+    pub fn insert(&self, n: u64, value: u64) {
+        self.map.insert(n, value);
+    }
+
+    /// How many `n`s are in the cache.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::find_nth_prime;
+    use crate::number_theory::prime_factorization;
+
+    #[test]
+    fn second_search_is_a_hit() {
+        clear_thread_cache();
+        // Past the first 10000 primes, which come from a table and never touch the cache
+        assert_eq!(find_nth_prime(10_100), 105_943);
+        let (hits, _) = thread_cache_stats();
+        assert_eq!(find_nth_prime(10_200), 107_033);
+        assert!(thread_cache_stats().0 > hits);
+        assert_eq!(thread_cache_lookup(10_150), Some((10_100, 105_943)));
+    }
+
+    #[test]
+    fn primality_cache_agrees_with_is_prime() {
+        let cache = PrimalityCache::new();
+        assert!(cache.is_empty());
+        for _ in 0..2 {
+            for n in 0..1000 {
+                assert_eq!(cache.is_prime_cached(n), crate::is_prime(n), "wrong answer for {}", n);
+            }
+        }
+        // The second pass was all hits
+        assert_eq!(cache.computed(), 1000);
+        assert_eq!(cache.len(), 1000);
+    }
+
+    #[test]
+    fn cached_factorizations_match() {
+        let cache = PrimalityCache::new();
+        for n in (0..100_000).step_by(997) {
+            assert_eq!(prime_factorization(n, Some(&cache)), prime_factorization(n, None));
+        }
+        assert_eq!(prime_factorization(360, Some(&cache)), vec![2, 2, 2, 3, 3, 5]);
+    }
+}
@@ -0,0 +1,211 @@
+//! Cancelling prime searches, both one at a time and all at once.
+//!
+//! A [CancellationToken] is a flag that can be set once, along with a future that resolves when it
+//! is. Tokens form a tree: cancelling a token cancels all of its children too. So a single global
+//! [shutdown_token] can stop everything, while each search gets its own child token that can be
+//! cancelled on its own.
+//!
+//! A search that's already running on a blocking thread can't be interrupted from the outside, so
+//! [prime_output_cancelable] checks its token every [crate::CANCELLATION_CHECK_INTERVAL] candidates and
+//! gives up if it has been cancelled.
+
+use crate::{find_nth_prime_interruptible, try_time_search, PrimeResult};
+use futures::future::poll_fn;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::task::{Context, Poll, Waker};
+use tokio_executor::threadpool::blocking;
+
+/// A flag that can be set once, and waited on.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    /// The wakers of the [Cancelled] futures waiting on this token, keyed by [Cancelled::key]
+    waiters: Mutex<HashMap<u64, Waker>>,
+    next_key: AtomicU64,
+    children: Mutex<Vec<Weak<Inner>>>,
+}
+
+impl Inner {
+    fn cancel(&self) {
+        if self.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        for (_, waker) in self.waiters.lock().unwrap().drain() {
+            waker.wake();
+        }
+        for child in self.children.lock().unwrap().drain(..) {
+            if let Some(child) = child.upgrade() {
+                child.cancel();
+            }
+        }
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// A new token that is cancelled whenever this one is. Cancelling the child leaves this token
+    /// alone.
+    pub fn child_token(&self) -> CancellationToken {
+        let child = CancellationToken::new();
+        let mut children = self.inner.children.lock().unwrap();
+        // Forget any children that have since been dropped, or a long-lived token like the
+        // shutdown token would hang on to one for every child it ever had
+        children.retain(|c| c.strong_count() > 0);
+        children.push(Arc::downgrade(&child.inner));
+        drop(children);
+        // If we were cancelled while adding the child, we may have missed it
+        if self.is_cancelled() {
+            child.cancel();
+        }
+        child
+    }
+
+    /// Cancel this token and all of its children.
+    pub fn cancel(&self) {
+        self.inner.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// A future that resolves once the token is cancelled.
+    pub fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled { token: self, key: None }
+    }
+}
+
+/// The future returned by [CancellationToken::cancelled]. Dropping it before the token is
+/// cancelled takes its waker back off the token.
+pub struct Cancelled<'a> {
+    token: &'a CancellationToken,
+    /// Where our waker is in the token's waiters, once we've registered one
+    key: Option<u64>,
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let inner = &self.token.inner;
+        // Check the flag while holding the lock, so we can't miss a `cancel` that happens after
+        // we've checked but before we've registered
+        let mut waiters = inner.waiters.lock().unwrap();
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+        let key = *self.key.get_or_insert_with(|| inner.next_key.fetch_add(1, Ordering::Relaxed));
+        match waiters.get_mut(&key) {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            Some(waker) => *waker = cx.waker().clone(),
+            None => {
+                waiters.insert(key, cx.waker().clone());
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Cancelled<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.token.inner.waiters.lock().unwrap().remove(&key);
+        }
+    }
+}
+
+/// The token that every search should be a child of. Cancel it to stop everything.
+pub fn shutdown_token() -> &'static CancellationToken {
+    static SHUTDOWN: OnceLock<CancellationToken> = OnceLock::new();
+    SHUTDOWN.get_or_init(CancellationToken::new)
+}
+
+/// Search for the `n`th prime on a blocking thread, giving up and returning `None` if `token` is
+/// cancelled. A search whose token is cancelled before it starts never takes up a blocking thread
+/// at all.
+///
+/// This is the slow search from [crate::try_prime_output], not the sieves [crate::prime_output]
+/// uses for big `n`, because it's the one that checks in as it goes.
+pub async fn prime_output_cancelable(id: u64, n: u64, token: CancellationToken) -> Option<PrimeResult> {
+    if token.is_cancelled() {
+        return None;
+    }
+    poll_fn(|_| {
+        blocking(|| try_time_search(id, n, |n| find_nth_prime_interruptible(n, || token.is_cancelled())))
+    }).await.expect("Couldn't block")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_runtime;
+    use futures::future::FutureExt;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn cancelled_before_start_returns_none() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let started = Instant::now();
+        // It never gets as far as blocking, so it doesn't even need spawning
+        let result = test_runtime(1).block_on(prime_output_cancelable(0, 1_000_000_000, token));
+        assert_eq!(result, None);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn uncancelled_search_finishes() {
+        crate::prime_test! {
+            let search = crate::spawn_with_handle(prime_output_cancelable(0, 100, CancellationToken::new()));
+            assert_eq!(search.await.map(|r| r.value), Some(541));
+        }
+    }
+
+    #[test]
+    fn cancelling_a_parent_cancels_its_children() {
+        let parent = CancellationToken::new();
+        let child = parent.child_token();
+        let grandchild = child.child_token();
+        child.cancel();
+        assert!(!parent.is_cancelled());
+        assert!(grandchild.is_cancelled());
+        let other = parent.child_token();
+        parent.cancel();
+        assert!(other.is_cancelled());
+        assert!(parent.child_token().is_cancelled());
+    }
+
+    #[test]
+    fn dropped_children_are_forgotten() {
+        let parent = CancellationToken::new();
+        for _ in 0..100 {
+            parent.child_token();
+        }
+        let _kept = parent.child_token();
+        assert_eq!(parent.inner.children.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn dropped_waiters_are_forgotten() {
+        let token = CancellationToken::new();
+        assert_eq!(token.cancelled().now_or_never(), None);
+        assert!(token.inner.waiters.lock().unwrap().is_empty());
+        let mut waiting = token.cancelled();
+        assert_eq!((&mut waiting).now_or_never(), None);
+        assert_eq!(token.inner.waiters.lock().unwrap().len(), 1);
+        token.cancel();
+        assert_eq!(waiting.now_or_never(), Some(()));
+    }
+}
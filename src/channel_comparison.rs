@@ -0,0 +1,97 @@
+//! Two ways to get results from blocking threads back to async code over a channel.
+//!
+//! With Tokio's `mpsc`, the blocking closure hands its result back as the output of `blocking`,
+//! and the async side of the task sends it on. Sending is async, so a full channel just makes the
+//! task wait, and the receiver is woken as soon as there's something to receive.
+//!
+//! With `crossbeam_channel`, the blocking closure sends the result itself, straight from the
+//! blocking thread. A crossbeam channel knows nothing about async, though: a full channel blocks
+//! the sending thread (which is fine, it's a blocking thread), and an empty one has no way to wake
+//! a task when something arrives. [collect_via_crossbeam] gets around that by polling with
+//! `try_recv`, and asking to be polled again straight away whenever the channel is empty. That
+//! keeps latency low, but it keeps one scheduler thread spinning for as long as it's waiting.
+
+use crate::primality::find_nth_prime_with_sieve_fallback;
+use crate::{prime_output, time_search, PrimeResult};
+use crossbeam_channel::{Receiver, TryRecvError};
+use futures::future::poll_fn;
+use std::task::Poll;
+use tokio::sync::mpsc;
+use tokio_executor::threadpool::blocking;
+
+/// How many results either channel holds before senders have to wait.
+pub const CHANNEL_CAPACITY: usize = 20;
+
+/// The next message from `rx`, or `None` once every sender has gone, without blocking the thread.
+async fn recv_async<T>(rx: &Receiver<T>) -> Option<T> {
+    poll_fn(|cx| match rx.try_recv() {
+        Ok(message) => Poll::Ready(Some(message)),
+        Err(TryRecvError::Disconnected) => Poll::Ready(None),
+        Err(TryRecvError::Empty) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }).await
+}
+
+/// Run all the `(id, n)` searches at once, each sending its result from its blocking thread on a
+/// `crossbeam_channel`, and collect the results in the order they arrive. Like
+/// [crate::prime_output], this has to run inside the thread pool runtime.
+pub async fn collect_via_crossbeam(tasks: Vec<(u64, u64)>) -> Vec<PrimeResult> {
+    let (tx, rx) = crossbeam_channel::bounded(CHANNEL_CAPACITY);
+    for (id, n) in tasks {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            poll_fn(|_| blocking(|| tx.send(time_search(id, n, find_nth_prime_with_sieve_fallback)).ok())).await.expect("Couldn't block");
+        });
+    }
+    // Otherwise the channel would never disconnect, and we'd wait forever
+    drop(tx);
+    let mut results = Vec::new();
+    while let Some(result) = recv_async(&rx).await {
+        results.push(result);
+    }
+    results
+}
+
+/// The same as [collect_via_crossbeam], but each search's task sends its result on a Tokio
+/// `mpsc` channel once its blocking closure is done.
+pub async fn collect_via_tokio_mpsc(tasks: Vec<(u64, u64)>) -> Vec<PrimeResult> {
+    let (tx, mut rx) = mpsc::channel(CHANNEL_CAPACITY);
+    for (id, n) in tasks {
+        let mut tx = tx.clone();
+        tokio::spawn(async move {
+            tx.send(prime_output(id, n).await).await.ok();
+        });
+    }
+    drop(tx);
+    let mut results = Vec::new();
+    while let Some(result) = rx.recv().await {
+        results.push(result);
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tables::OEIS_A000040_FIRST_1000;
+
+    /// The `(id, value)` of every result, sorted by id.
+    fn by_id(mut results: Vec<PrimeResult>) -> Vec<(u64, u64)> {
+        results.sort_by_key(|r| r.id);
+        results.into_iter().map(|r| (r.id, r.value)).collect()
+    }
+
+    #[test]
+    fn both_channels_deliver_every_result() {
+        // More searches than either channel has room for, so some senders have to wait
+        let tasks: Vec<_> = (0..50).map(|id| (id, 1000 - id * 10)).collect();
+        let expected: Vec<_> = tasks.iter().map(|&(id, n)| (id, OEIS_A000040_FIRST_1000[n as usize - 1])).collect();
+        let (crossbeam, tokio) = crate::prime_test! {
+            (collect_via_crossbeam(tasks.clone()).await, collect_via_tokio_mpsc(tasks).await)
+        };
+        assert_eq!(by_id(crossbeam), expected);
+        assert_eq!(by_id(tokio), expected);
+    }
+}
@@ -0,0 +1,76 @@
+//! Command line flags for the demo binary. There are only a handful of them, so we parse them by
+//! hand rather than pull in an argument parsing crate.
+
+use std::str::FromStr;
+
+/// The options the demo was started with.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Options {
+    /// Print a table of the peak memory used by each search once they're all done.
+    pub track_memory: bool,
+    /// Check the results against the sieve, and the small ones against OEIS A000040, once they're
+    /// all done.
+    pub verify: bool,
+    /// Instead of the usual demo, run the searches twice and compare completion order with
+    /// submission order.
+    pub compare_orderings: bool,
+    /// Instead of the usual demo, print an Ulam spiral this big.
+    pub spiral: Option<usize>,
+    /// Print a histogram of the gaps between all the primes up to the largest one found.
+    pub histogram: bool,
+    /// Show a spinner while the searches run, and print the results in a table at the end.
+    pub spinner: bool,
+    /// Pin each of the runtime's threads to a CPU core.
+    pub cpu_affinity: bool,
+    /// Instead of the usual demo, feed the searches through a pool that only runs 5 at a time,
+    /// collecting the results a batch at a time.
+    pub pool: bool,
+    /// Print a chart of when each search waited and when it ran, once they're all done.
+    pub timeline: bool,
+    /// Instead of the usual demo, run the default stress test of the blocking pool.
+    pub stress_test: bool,
+    /// Instead of the usual demo, print how the prime count compares with `li(x)` up to 10^7.
+    pub pi_vs_li: bool,
+}
+
+impl Options {
+    /// Parse the options from the process arguments.
+    pub fn from_args() -> Result<Options, String> {
+        Options::parse(std::env::args().skip(1))
+    }
+
+    /// Parse the options from `args`, which should not include the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--track-memory" => options.track_memory = true,
+                "--verify" => options.verify = true,
+                "--compare-orderings" => options.compare_orderings = true,
+                "--histogram" => options.histogram = true,
+                "--spinner" => options.spinner = true,
+                "--cpu-affinity" => options.cpu_affinity = true,
+                "--pool" => options.pool = true,
+                "--timeline" => options.timeline = true,
+                "--stress-test" => options.stress_test = true,
+                "--pi-vs-li" => options.pi_vs_li = true,
+                "--spiral" => options.spiral = Some(parse_value(&arg, args.next())?),
+                _ => return Err(format!("Unknown argument: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Whether any of the options need the results collected once the searches are done, rather
+    /// than just printed as they come in.
+    pub fn needs_results(&self) -> bool {
+        self.track_memory || self.verify || self.histogram || self.spinner || self.timeline
+    }
+}
+
+/// Parse the value that follows the flag `flag`.
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", flag))?;
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
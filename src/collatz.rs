@@ -0,0 +1,75 @@
+//! Collatz sequences that start at a prime.
+//!
+//! Start anywhere, then halve the number if it's even, or triple it and add one if it's odd. The
+//! Collatz conjecture says you always get to 1 eventually. Nobody has proved it, though it's been
+//! checked for every start below 2^68. There's nothing special about the primes here; they're
+//! just a handy supply of starting points, and a different job to hand to the blocking threads.
+
+use crate::primality::{find_nth_prime_auto, PrimeIterator};
+use crate::spawn_with_handle;
+use futures::future::{join_all, poll_fn};
+use tokio_executor::threadpool::blocking;
+
+/// How many steps it takes the Collatz sequence from `n` to reach 1: `collatz_length(7)` is 16,
+/// and `collatz_length(1)` is 0. Panics if `n` is 0, which never gets there.
+///
+/// Only the current value is kept, not the sequence. Sequences can climb well above where they
+/// start, so that's held in a `u128`.
+pub fn collatz_length(n: u64) -> u64 {
+    assert!(n > 0, "The Collatz sequence from 0 never reaches 1");
+    let (mut x, mut steps) = (n as u128, 0);
+    while x != 1 {
+        x = if x % 2 == 0 {
+            x / 2
+        } else {
+            x.checked_mul(3).and_then(|x| x.checked_add(1)).expect("The sequence overflowed a u128")
+        };
+        steps += 1;
+    }
+    steps
+}
+
+/// The Collatz lengths of `count` consecutive primes, starting from the `start_n`th, printed as a
+/// table and returned as `(prime, steps)` pairs.
+///
+/// Finding the first prime and each of the lengths all get blocking threads of their own, so this
+/// has to be `spawn`ed onto the runtime.
+pub async fn prime_collatz_analysis(start_n: u64, count: usize) -> Vec<(u64, u64)> {
+    let first = find_nth_prime_auto(start_n).await;
+    let lengths = PrimeIterator::starting_from(first).take(count).map(|p| {
+        spawn_with_handle(async move {
+            let steps = poll_fn(|_| blocking(|| collatz_length(p))).await.expect("Couldn't block");
+            (p, steps)
+        })
+    });
+    let rows = join_all(lengths.collect::<Vec<_>>()).await;
+    println!("{:>20}  {:>5}", "prime", "steps");
+    for &(p, steps) in &rows {
+        println!("{:>20}  {:>5}", p, steps);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collatz_lengths() {
+        assert_eq!(collatz_length(7), 16);
+        assert_eq!(collatz_length(1), 0);
+        assert_eq!(collatz_length(27), 111);
+    }
+
+    #[test]
+    #[should_panic(expected = "never reaches 1")]
+    fn zero_has_no_length() {
+        collatz_length(0);
+    }
+
+    #[test]
+    fn the_first_five_primes() {
+        let rows = crate::prime_test! { crate::spawn_with_handle(prime_collatz_analysis(1, 5)).await };
+        assert_eq!(rows, vec![(2, 1), (3, 7), (5, 5), (7, 16), (11, 14)]);
+    }
+}
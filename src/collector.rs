@@ -0,0 +1,51 @@
+//! Getting results back in the order the work was submitted.
+//!
+//! This is the simplest way to do it: spawn everything up front, keep the handles in a `Vec`, then
+//! await the handles one after the other. All the searches still run concurrently; we just don't
+//! look at a result until we've seen all the ones submitted before it.
+//!
+//! Compare that with `FuturesUnordered` (see [crate::stream::compare_orderings]), which hands you
+//! each result the moment it's ready. That's what you want if you can use results as they arrive.
+//! Here a slow early search holds up every result behind it, even ones that finished long ago. In
+//! exchange, there's no reordering to do, and no buffer of early arrivals to manage like
+//! [crate::stream::SortedResultStream] has.
+
+use crate::{prime_output, spawn_with_handle, PrimeResult};
+use std::future::Future;
+
+/// Spawn every future in `searches`, and return their results in the same order as `searches`.
+pub async fn collect_in_order<I, F>(searches: I) -> Vec<PrimeResult>
+where
+    I: IntoIterator<Item = F>,
+    F: Future<Output = PrimeResult> + Send + 'static,
+{
+    // Spawn everything before awaiting anything, or the searches would run one at a time
+    let handles = searches.into_iter().map(spawn_with_handle).collect::<Vec<_>>();
+    let mut results = Vec::with_capacity(handles.len());
+    for handle in handles {
+        results.push(handle.await);
+    }
+    results
+}
+
+/// Run the `(id, n)` searches concurrently, and return the results in the same order as `tasks`.
+pub async fn prime_output_ordered_collector(tasks: Vec<(u64, u64)>) -> Vec<PrimeResult> {
+    collect_in_order(tasks.into_iter().map(|(id, n)| prime_output(id, n))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verification::verify_nth_prime;
+
+    #[test]
+    fn results_come_back_in_submission_order() {
+        crate::prime_test! {
+            let ns = [200_000, 10, 50_000, 100, 150_000, 1, 20_000, 1000, 100_000, 5];
+            let tasks = ns.iter().enumerate().map(|(i, &n)| (9 - i as u64, n)).collect::<Vec<_>>();
+            let results = prime_output_ordered_collector(tasks.clone()).await;
+            assert_eq!(results.iter().map(|r| (r.id, r.n)).collect::<Vec<_>>(), tasks);
+            assert!(results.iter().all(|r| verify_nth_prime(r.n, r.value)));
+        }
+    }
+}
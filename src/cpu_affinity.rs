@@ -0,0 +1,159 @@
+//! Pinning the runtime's threads to particular CPU cores.
+//!
+//! Left to itself, the OS moves threads between cores whenever it likes, and a thread that moves
+//! leaves its warm caches behind. On big machines, especially NUMA ones where some memory is
+//! closer to some cores than others, keeping each thread on one core can make CPU-heavy work
+//! noticeably quicker. On a laptop it rarely makes a difference.
+//!
+//! You can pin every thread in the runtime with [pin_threads_to_cores], or run particular groups
+//! of searches on particular cores with [run_with_affinity_groups].
+
+use crate::primality::find_nth_prime_with_sieve_fallback;
+use crate::{spawn_prime_output, time_search, PrimeResult};
+use core_affinity::CoreId;
+use futures::channel::oneshot;
+use futures::future::{join_all, BoxFuture, FutureExt};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use tokio::runtime::Builder;
+
+/// The cores this process is allowed to run on.
+pub fn get_available_cores() -> Vec<CoreId> {
+    core_affinity::get_core_ids().unwrap_or_default()
+}
+
+/// Pin the calling thread to `core`. Returns `false` if the OS wouldn't let us, or if `core` isn't
+/// one of the [get_available_cores].
+pub fn bind_current_thread_to_core(core: CoreId) -> bool {
+    // `core_affinity` panics on ids too big for the OS's CPU set, rather than reporting an error
+    get_available_cores().contains(&core) && core_affinity::set_for_current(core)
+}
+
+/// Pin each thread `builder` starts to the next core in turn, wrapping around once every core has
+/// a thread.
+///
+/// There's no way to tell a blocking thread from a core thread when it starts. In this version of
+/// Tokio they're the same threads anyway: a worker that calls `blocking` becomes a blocking thread,
+/// and another thread takes over its work queue. So this pins all of them, in whatever order the
+/// pool happens to start them.
+pub fn pin_threads_to_cores(builder: &mut Builder) -> &mut Builder {
+    let cores = get_available_cores();
+    if cores.is_empty() {
+        return builder;
+    }
+    let next = AtomicUsize::new(0);
+    builder.after_start(move || {
+        let core = cores[next.fetch_add(1, Ordering::Relaxed) % cores.len()];
+        bind_current_thread_to_core(core);
+    })
+}
+
+/// Searches that should run together, on one core if `preferred_core` says which.
+///
+/// Searches for nearby primes go over the same numbers, so running them one after another on the
+/// same core leaves the caches warm for the next one.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AffinityGroup {
+    /// The `(id, n)` searches in the group
+    pub tasks: Vec<(u64, u64)>,
+    /// The id of the core to run the group on, as in [CoreId::id], or `None` to run the searches
+    /// on the runtime's blocking threads like any others
+    pub preferred_core: Option<usize>,
+}
+
+/// Run the searches for a group with a preferred core one after another, on a thread of their own
+/// pinned to that core. If the core doesn't exist, or the OS won't pin the thread, they still run
+/// there, just unpinned.
+fn run_pinned_group(tasks: Vec<(u64, u64)>, core: usize) -> Vec<oneshot::Receiver<PrimeResult>> {
+    let (senders, receivers): (Vec<_>, Vec<_>) = tasks.iter().map(|_| oneshot::channel()).unzip();
+    thread::spawn(move || {
+        bind_current_thread_to_core(CoreId { id: core });
+        for ((id, n), tx) in tasks.into_iter().zip(senders) {
+            tx.send(time_search(id, n, find_nth_prime_with_sieve_fallback)).ok();
+        }
+    });
+    receivers
+}
+
+/// Run the searches in every group, and return all their results, in the same order as the groups
+/// and the tasks within them.
+///
+/// Each group with a `preferred_core` gets a thread of its own, pinned to that core, which runs
+/// the group's searches in order. Tokio has no way to send a particular task to a particular one
+/// of its blocking threads, so these threads are outside the runtime altogether. The groups
+/// without one are spawned onto the runtime as usual, so the returned future has to run inside the
+/// thread pool runtime.
+pub async fn run_with_affinity_groups(groups: Vec<AffinityGroup>) -> Vec<PrimeResult> {
+    let mut searches: Vec<BoxFuture<'static, PrimeResult>> = Vec::new();
+    for group in groups {
+        match group.preferred_core {
+            Some(core) => {
+                let receivers = run_pinned_group(group.tasks, core).into_iter();
+                searches.extend(receivers.map(|rx| rx.map(|r| r.expect("A pinned search panicked")).boxed()));
+            }
+            None => searches.extend(group.tasks.into_iter().map(|(id, n)| spawn_prime_output(id, n).boxed())),
+        }
+    }
+    join_all(searches).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn_with_handle;
+    use futures::future::poll_fn;
+    use std::sync::{Arc, Barrier};
+    use tokio_executor::threadpool::blocking;
+
+    #[test]
+    #[ignore = "needs at least 4 cores"]
+    fn blocking_threads_get_a_core_each() {
+        assert!(get_available_cores().len() >= 4, "This test needs at least 4 cores");
+        let rt = pin_threads_to_cores(Builder::new().core_threads(1).blocking_threads(4))
+            .build()
+            .expect("Couldn't build the runtime");
+        // Hold every job until all 4 are running, so that they can't share a thread
+        let barrier = Arc::new(Barrier::new(4));
+        let cores = rt.block_on(async move {
+            let jobs = (0..4).map(|_| {
+                let barrier = barrier.clone();
+                spawn_with_handle(async move {
+                    poll_fn(|_| {
+                        blocking(|| {
+                            barrier.wait();
+                            // A pinned thread is only allowed on the one core
+                            get_available_cores()
+                        })
+                    })
+                    .await
+                    .expect("Couldn't block")
+                })
+            });
+            join_all(jobs.collect::<Vec<_>>()).await
+        });
+        let mut ids = cores.iter().map(|c| c.iter().map(|c| c.id).collect::<Vec<_>>()).collect::<Vec<_>>();
+        assert!(ids.iter().all(|c| c.len() == 1), "Not every thread was pinned: {:?}", ids);
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 4, "Some threads share a core: {:?}", ids);
+    }
+
+    #[test]
+    fn groups_without_a_core_run_like_any_other_searches() {
+        let groups = vec![
+            AffinityGroup { tasks: vec![(0, 1000), (1, 10)], preferred_core: None },
+            AffinityGroup { tasks: Vec::new(), preferred_core: None },
+            AffinityGroup { tasks: vec![(2, 100)], preferred_core: None },
+        ];
+        let results = crate::prime_test! { run_with_affinity_groups(groups).await };
+        let values: Vec<_> = results.iter().map(|r| (r.id, r.value)).collect();
+        assert_eq!(values, vec![(0, 7919), (1, 29), (2, 541)]);
+    }
+
+    #[test]
+    fn pinned_groups_still_run_on_a_missing_core() {
+        let groups = vec![AffinityGroup { tasks: vec![(0, 10), (1, 100)], preferred_core: Some(usize::MAX) }];
+        let results = crate::prime_test! { run_with_affinity_groups(groups).await };
+        assert_eq!(results.iter().map(|r| r.value).collect::<Vec<_>>(), vec![29, 541]);
+    }
+}
@@ -0,0 +1,187 @@
+//! Searches that need the results of other searches before they can start.
+//!
+//! A [TaskGraph] holds a set of tasks, each of which can depend on any of the tasks added before
+//! it. [TaskGraph::execute] starts every task whose dependencies are done, all at once, and starts
+//! the rest as soon as their last dependency finishes. Since a task can only depend on tasks that
+//! already exist, there's no way to build a cycle.
+//!
+//! [twin_primes_after] is an example: each twin prime search needs to know where to start, which
+//! is the answer to a separate `n`th prime search.
+
+use crate::primality::{find_nth_prime_auto, is_prime_fast, PrimeIterator};
+use futures::future::{poll_fn, BoxFuture, FutureExt};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::future::Future;
+use tokio_executor::threadpool::blocking;
+
+/// Identifies a task in a [TaskGraph], so that later tasks can depend on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaskId(usize);
+
+type Task<T> = Box<dyn FnOnce(Vec<T>) -> BoxFuture<'static, T> + Send>;
+
+struct Node<T> {
+    task: Task<T>,
+    deps: Vec<TaskId>,
+}
+
+/// A set of tasks, some of which depend on the outputs of others.
+pub struct TaskGraph<T> {
+    nodes: Vec<Node<T>>,
+}
+
+impl<T> Default for TaskGraph<T> {
+    fn default() -> Self {
+        TaskGraph { nodes: Vec::new() }
+    }
+}
+
+impl<T: Clone + Send + 'static> TaskGraph<T> {
+    pub fn new() -> Self {
+        TaskGraph::default()
+    }
+
+    /// Add a task that waits for each of `deps` to finish, then calls `task` with their outputs
+    /// (in the same order as `deps`) and runs the future it returns. A task with no dependencies
+    /// gets an empty `Vec`.
+    ///
+    /// Panics if any of `deps` isn't from this graph.
+    pub fn add_task<F, Fut>(&mut self, task: F, deps: &[TaskId]) -> TaskId
+    where
+        F: FnOnce(Vec<T>) -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        let id = TaskId(self.nodes.len());
+        assert!(deps.iter().all(|dep| dep.0 < id.0), "A task can only depend on tasks in the same graph");
+        let task: Task<T> = Box::new(move |inputs| task(inputs).boxed());
+        self.nodes.push(Node { task, deps: deps.to_vec() });
+        id
+    }
+
+    /// How many tasks are in the graph.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Run every task, each one as soon as its dependencies are done, and return their outputs in
+    /// the order the tasks were added.
+    ///
+    /// The tasks are all polled from this one future, so for searches to actually run in parallel,
+    /// each task should hand its work to a blocking thread, or `spawn` it.
+    pub async fn execute(self) -> Vec<T> {
+        let count = self.nodes.len();
+        let mut waiting_on = self.nodes.iter().map(|node| node.deps.len()).collect::<Vec<_>>();
+        let mut dependents = vec![Vec::new(); count];
+        for (i, node) in self.nodes.iter().enumerate() {
+            for dep in &node.deps {
+                dependents[dep.0].push(i);
+            }
+        }
+        let mut nodes = self.nodes.into_iter().map(Some).collect::<Vec<_>>();
+        let mut outputs: Vec<Option<T>> = vec![None; count];
+        let mut running = FuturesUnordered::new();
+        let mut start = |i: usize, outputs: &[Option<T>]| {
+            let node = nodes[i].take().expect("A task was started twice");
+            let inputs = node.deps.iter().map(|dep| outputs[dep.0].clone().expect("A dependency isn't done"));
+            let inputs = inputs.collect();
+            (node.task)(inputs).map(move |output| (i, output))
+        };
+        for i in (0..count).filter(|&i| waiting_on[i] == 0) {
+            running.push(start(i, &outputs));
+        }
+        while let Some((i, output)) = running.next().await {
+            outputs[i] = Some(output);
+            for &d in &dependents[i] {
+                waiting_on[d] -= 1;
+                if waiting_on[d] == 0 {
+                    running.push(start(d, &outputs));
+                }
+            }
+        }
+        outputs.into_iter().map(|output| output.expect("A task never ran")).collect()
+    }
+}
+
+/// The first pair of twin primes `(p, p + 2)` with `p` at least `start`.
+pub fn first_twin_primes_from(start: u64) -> (u64, u64) {
+    PrimeIterator::starting_from(start)
+        .find(|&p| is_prime_fast(p + 2))
+        .map(|p| (p, p + 2))
+        .expect("Ran out of primes below u64::MAX")
+}
+
+/// For each `n` in `ns`, the first pair of twin primes from the `n`th prime on.
+///
+/// Each of these is two tasks in a [TaskGraph]: one finds the `n`th prime, and the other, which
+/// depends on it, looks for twin primes from there. The `n`th prime searches all run at once, and
+/// each twin prime search starts as soon as its own `n`th prime is known. Both run on blocking
+/// threads, so this has to be `spawn`ed onto the runtime.
+pub async fn twin_primes_after(ns: Vec<u64>) -> Vec<(u64, u64)> {
+    let mut graph = TaskGraph::new();
+    let mut twins = Vec::new();
+    for n in ns {
+        let nth = graph.add_task(move |_| find_nth_prime_auto(n), &[]);
+        let twin = graph.add_task(
+            |inputs: Vec<u64>| {
+                let start = inputs[0];
+                poll_fn(move |_| blocking(|| first_twin_primes_from(start).0)).map(|r| r.expect("Couldn't block"))
+            },
+            &[nth],
+        );
+        twins.push(twin);
+    }
+    let outputs = graph.execute().await;
+    twins.into_iter().map(|twin| (outputs[twin.0], outputs[twin.0] + 2)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn_with_handle;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn chains_wait_and_independent_tasks_overlap() {
+        crate::prime_test! {
+            let (running, most) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+            let log = Arc::new(Mutex::new(Vec::new()));
+            // Each task sleeps a while, logs that it ran, and adds 1 to the sum of its inputs
+            let task = |name: &'static str| {
+                let (running, most, log) = (running.clone(), most.clone(), log.clone());
+                move |inputs: Vec<u64>| async move {
+                    most.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    tokio::timer::delay(tokio::clock::now() + Duration::from_millis(20)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    log.lock().unwrap().push(name);
+                    inputs.iter().sum::<u64>() + 1
+                }
+            };
+            let mut graph = TaskGraph::new();
+            let a = graph.add_task(task("a"), &[]);
+            let b = graph.add_task(task("b"), &[a]);
+            graph.add_task(task("c"), &[b]);
+            for _ in 0..3 {
+                graph.add_task(task("independent"), &[]);
+            }
+            assert_eq!(graph.len(), 6);
+            assert_eq!(graph.execute().await, vec![1, 2, 3, 1, 1, 1]);
+            // The first link of the chain ran alongside all three independent tasks
+            assert_eq!(most.load(Ordering::SeqCst), 4);
+            let log = log.lock().unwrap();
+            let position = |name| log.iter().position(|&n| n == name).unwrap();
+            assert!(position("a") < position("b") && position("b") < position("c"));
+        }
+    }
+
+    #[test]
+    fn twin_primes_start_from_the_nth_prime() {
+        let twins = crate::prime_test! { spawn_with_handle(twin_primes_after(vec![1, 10, 1000])).await };
+        assert_eq!(twins, vec![(3, 5), (29, 31), (7949, 7951)]);
+    }
+}
@@ -0,0 +1,64 @@
+//! Chaining a blocking search and some cheap arithmetic on its result.
+//!
+//! Only the expensive part needs a blocking thread. Once the search hands its answer back, adding
+//! up the digits is a handful of instructions, so there's no point queueing it up for a blocking
+//! thread of its own. Do it right there in the async code:
+//!
+//! ```text
+//! async {
+//!     let x = blocking_computation().await;
+//!     pure_transform(x)
+//! }
+//! ```
+//!
+//! Handing off to a blocking thread costs far more than the digit sum, and that same thread
+//! could be getting on with the next search instead. The rule of thumb: if it runs quicker than
+//! the hand-off, keep it on the async side.
+
+use crate::primality::find_nth_prime_auto;
+use crate::spawn_with_handle;
+use futures::future::join_all;
+
+/// The sum of the decimal digits of `n`.
+pub fn digit_sum(mut n: u64) -> u64 {
+    let mut sum = 0;
+    while n > 0 {
+        sum += n % 10;
+        n /= 10;
+    }
+    sum
+}
+
+/// The digit sum of the `n`th prime. Finding the prime needs a blocking thread, so this has to be
+/// `spawn`ed onto the runtime.
+pub async fn sum_of_digits_of_nth_prime(n: u64) -> u64 {
+    let prime = find_nth_prime_auto(n).await;
+    digit_sum(prime)
+}
+
+/// The digit sums of the `n`th primes for every `n` in `ns`, all searched for at once. The sums
+/// come back in the same order as `ns`.
+pub async fn sum_digits_parallel(ns: Vec<u64>) -> Vec<u64> {
+    let handles = ns.into_iter().map(|n| spawn_with_handle(sum_of_digits_of_nth_prime(n))).collect::<Vec<_>>();
+    join_all(handles).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digit_sums() {
+        assert_eq!(digit_sum(0), 0);
+        assert_eq!(digit_sum(29), 11);
+        assert_eq!(digit_sum(u64::MAX), 87);
+    }
+
+    #[test]
+    fn tenth_prime_sums_to_11() {
+        crate::prime_test! {
+            assert_eq!(spawn_with_handle(sum_of_digits_of_nth_prime(10)).await, 11);
+            assert_eq!(sum_digits_parallel(vec![10, 100, 1000]).await, vec![11, 10, 26]);
+        }
+    }
+}
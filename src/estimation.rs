@@ -0,0 +1,78 @@
+//! Bounds on the `n`th prime that are proven to hold.
+//!
+//! The estimates in [crate::approx] are as close as they can get, but they can land on either side
+//! of the answer. Anything that sieves "up to the `n`th prime" needs a limit that's guaranteed to
+//! be big enough, and these are the tightest ones with proofs behind them. They're from Pierre
+//! Dusart, "Estimates of some functions over primes without R.H." (2010), arXiv:1002.0442, along
+//! with Rosser's classic `n (ln n + ln ln n)` for small `n`.
+
+use crate::tables::kth_prime_table;
+
+/// `ln n` and `ln ln n`.
+fn logs(n: u64) -> (f64, f64) {
+    let ln = (n as f64).ln();
+    (ln, ln.ln())
+}
+
+/// A number the `n`th prime (counting from 1) is guaranteed not to be above. For `n` from 1 to 5
+/// it's exactly the `n`th prime, and for `n = 0` it's 0.
+///
+/// Dusart proved `p_n ≤ n (ln n + ln ln n - 1 + (ln ln n - 2) / ln n)` for `n ≥ 688,383`, which is
+/// within about 0.01% of the `n`th prime by the millionth. Below that we use his looser
+/// `n (ln n + ln ln n - 0.9484)` from `n = 39,017`, and Rosser's `n (ln n + ln ln n)` from `n = 6`.
+pub fn nth_prime_upper_bound(n: u64) -> u64 {
+    if n <= 5 {
+        return if n == 0 { 0 } else { kth_prime_table(n).expect("The table has the first few primes") };
+    }
+    let (ln, lnln) = logs(n);
+    let bound = match n {
+        0..=39_016 => ln + lnln,
+        39_017..=688_382 => ln + lnln - 0.9484,
+        _ => ln + lnln - 1.0 + (lnln - 2.0) / ln,
+    };
+    (n as f64 * bound).ceil() as u64
+}
+
+/// A number the `n`th prime (counting from 1) is guaranteed to be at least. For `n = 1` and 2
+/// it's exactly the `n`th prime, and for `n = 0` it's 0.
+///
+/// This is Dusart's companion bound, `p_n ≥ n (ln n + ln ln n - 1 + (ln ln n - 2.1) / ln n)`, which
+/// holds for every `n ≥ 3`.
+pub fn nth_prime_lower_bound(n: u64) -> u64 {
+    if n <= 2 {
+        return if n == 0 { 0 } else { kth_prime_table(n).expect("The table has the first few primes") };
+    }
+    let (ln, lnln) = logs(n);
+    (n as f64 * (ln + lnln - 1.0 + (lnln - 2.1) / ln)).floor() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tables::FIRST_10000_PRIMES;
+
+    #[test]
+    fn the_millionth_prime_is_between_the_bounds() {
+        assert!(nth_prime_upper_bound(1_000_000) >= 15_485_863);
+        assert!(nth_prime_lower_bound(1_000_000) <= 15_485_863);
+        // And the upper bound is within 0.01% of it
+        assert!(nth_prime_upper_bound(1_000_000) < 15_487_412);
+    }
+
+    #[test]
+    fn bounds_hold_for_the_first_10000_primes() {
+        assert_eq!((nth_prime_upper_bound(0), nth_prime_lower_bound(0)), (0, 0));
+        for (i, &p) in FIRST_10000_PRIMES.iter().enumerate() {
+            let n = i as u64 + 1;
+            assert!(nth_prime_lower_bound(n) <= p as u64 && p as u64 <= nth_prime_upper_bound(n), "{}", n);
+        }
+        assert_eq!((1..=5).map(nth_prime_upper_bound).collect::<Vec<_>>(), vec![2, 3, 5, 7, 11]);
+    }
+
+    #[test]
+    fn bounds_hold_either_side_of_each_switch() {
+        for &(n, p) in &[(39_016, 467_471), (39_017, 467_473), (688_382, 10_384_259), (688_383, 10_384_261)] {
+            assert!(nth_prime_lower_bound(n) <= p && p <= nth_prime_upper_bound(n), "{}", n);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! A tiny event bus, so that interested parties can hear about things happening inside long
+//! running batches without the batch having to know who's listening.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Things that can happen during a batch of searches.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A batch used up its CPU budget, and its remaining searches were cancelled.
+    BudgetExhausted { budget: Duration, spent: Duration },
+}
+
+/// Delivers every published event to every subscriber. The library's own events are [Event]s, but
+/// a bus can carry any kind of message that can be cloned for each subscriber.
+pub struct EventBus<E = Event> {
+    subscribers: Mutex<Vec<UnboundedSender<E>>>,
+}
+
+impl<E> Default for EventBus<E> {
+    fn default() -> Self {
+        EventBus { subscribers: Mutex::new(Vec::new()) }
+    }
+}
+
+impl<E: Clone> EventBus<E> {
+    pub fn new() -> Self {
+        EventBus::default()
+    }
+
+    /// A stream of every event published from now on.
+    pub fn subscribe(&self) -> UnboundedReceiver<E> {
+        let (tx, rx) = unbounded();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Send `event` to every subscriber. Subscribers that have hung up are dropped.
+    pub fn publish(&self, event: E) {
+        self.subscribers.lock().unwrap().retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+    }
+}
+
+/// The bus that the library publishes its events on.
+pub fn event_bus() -> &'static EventBus {
+    static BUS: OnceLock<EventBus> = OnceLock::new();
+    BUS.get_or_init(EventBus::new)
+}
@@ -0,0 +1,181 @@
+//! One search with every kind of bookkeeping this crate has, layered in the order they need to go.
+//!
+//! [prime_output_instrumented] is built from three layers, from the outside in:
+//!
+//! 1. [with_cache]: if the answer is already in the [PrimeCache], hand it straight back. Nothing
+//!    inside this layer runs at all, so a cache hit costs no blocking thread, and doesn't show up
+//!    as a search in the logs.
+//! 2. A `tracing` span for the search, entered every time the search is polled. `blocking` runs
+//!    its closure inside that poll, on the same thread, so everything the search logs is inside
+//!    the span (compare [crate::traced], which has to carry the span into a separately spawned
+//!    task by hand).
+//! 3. [timed_blocking_search]: the search itself on a blocking thread, timed, with a check for
+//!    cancellation once the thread is ready for it.
+//!
+//! Each layer is a function of its own, so they can be used separately, or stacked differently.
+//! Every search is counted in the [SharedStats], whichever layer it gets through to.
+
+use crate::cache::PrimeCache;
+use crate::cancellation::CancellationToken;
+use crate::metrics::SharedStats;
+use crate::primality::find_nth_prime_with_sieve_fallback;
+use crate::{time_search, PrimeResult};
+use futures::future::poll_fn;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_executor::threadpool::{blocking, BlockingError};
+use tracing::{info, info_span, Instrument};
+
+/// Why an instrumented search has no result.
+#[derive(Debug)]
+pub enum SearchError {
+    /// The search's token was cancelled before it started.
+    Cancelled,
+    /// The search couldn't get a blocking thread, usually because it wasn't spawned onto the
+    /// thread pool runtime.
+    Blocking(BlockingError),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::Cancelled => write!(f, "The search was cancelled"),
+            SearchError::Blocking(e) => write!(f, "The search couldn't block: {}", e),
+        }
+    }
+}
+
+impl Error for SearchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SearchError::Cancelled => None,
+            SearchError::Blocking(e) => Some(e),
+        }
+    }
+}
+
+/// Answer a search for the `n`th prime from `cache` if we can, and run `search` if we can't.
+///
+/// A hit is counted in `stats`, and comes back with an `elapsed` of zero. A successful search's
+/// answer goes in the cache for next time.
+pub async fn with_cache<F, Fut>(
+    cache: &PrimeCache,
+    stats: &SharedStats,
+    id: u64,
+    n: u64,
+    search: F,
+) -> Result<PrimeResult, SearchError>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<PrimeResult, SearchError>>,
+{
+    if let Some(value) = cache.get(n) {
+        stats.record_cache_hit();
+        info!(id = id, n = n, value = value, "cache hit");
+        return Ok(PrimeResult { id, n, value, started_at: Instant::now(), elapsed: Duration::ZERO, peak_memory: 0 });
+    }
+    let result = search().await?;
+    cache.insert(n, result.value);
+    Ok(result)
+}
+
+/// Search for the `n`th prime on a blocking thread, like [crate::prime_output], and record how
+/// long it waited and ran in `stats`. If `token` has been cancelled before the search starts, it
+/// doesn't run; a search that's cancelled before it even asks for a blocking thread doesn't take
+/// one up at all. This has to be `spawn`ed onto the runtime.
+pub async fn timed_blocking_search(
+    stats: &SharedStats,
+    token: &CancellationToken,
+    id: u64,
+    n: u64,
+) -> Result<PrimeResult, SearchError> {
+    let submitted = Instant::now();
+    if token.is_cancelled() {
+        stats.record_cancelled();
+        return Err(SearchError::Cancelled);
+    }
+    let result = poll_fn(|_| {
+        blocking(|| {
+            let wait = submitted.elapsed();
+            if token.is_cancelled() {
+                return None;
+            }
+            info!("searching for prime");
+            let result = time_search(id, n, find_nth_prime_with_sieve_fallback);
+            stats.record_computed(wait, result.elapsed);
+            info!(value = result.value, elapsed_ms = result.elapsed.as_millis() as u64, "found prime");
+            Some(result)
+        })
+    })
+    .await;
+    match result {
+        Ok(Some(result)) => Ok(result),
+        Ok(None) => {
+            stats.record_cancelled();
+            Err(SearchError::Cancelled)
+        }
+        Err(e) => {
+            stats.record_failed();
+            Err(SearchError::Blocking(e))
+        }
+    }
+}
+
+/// Search for the `n`th prime with all three layers from the [module docs](self): the cache, then
+/// a `prime_search` span, then the timed search on a blocking thread. Every call is counted in
+/// `stats`, along with how it turned out.
+///
+/// A cache hit comes straight back, but anything else needs a blocking thread, so this has to be
+/// `spawn`ed onto the runtime.
+pub async fn prime_output_instrumented(
+    id: u64,
+    n: u64,
+    stats: Arc<SharedStats>,
+    cache: Arc<PrimeCache>,
+    token: CancellationToken,
+) -> Result<PrimeResult, SearchError> {
+    stats.record_request();
+    with_cache(&cache, &stats, id, n, || {
+        timed_blocking_search(&stats, &token, id, n).instrument(info_span!("prime_search", id = id, n = n))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn cache_hits_skip_the_blocking_search_but_are_still_counted() {
+        let (stats, cache) = (Arc::new(SharedStats::new()), Arc::new(PrimeCache::new()));
+        let search = || prime_output_instrumented(1, 10_000, stats.clone(), cache.clone(), CancellationToken::new());
+        let first = crate::prime_test! { crate::spawn_with_handle(search()).await }.unwrap();
+        assert_eq!(first.value, 104_729);
+        assert_eq!(cache.get(10_000), Some(104_729));
+
+        // Off the thread pool, `blocking` would fail, so this can only succeed if it never blocks
+        let hit = block_on(search()).unwrap();
+        assert_eq!((hit.value, hit.elapsed), (104_729, Duration::ZERO));
+        assert_eq!((stats.requests(), stats.computed(), stats.cache_hits()), (2, 1, 1));
+
+        // Whereas a miss does try to block
+        let miss = block_on(prime_output_instrumented(2, 2, stats.clone(), cache, CancellationToken::new()));
+        assert!(matches!(miss, Err(SearchError::Blocking(_))));
+        assert_eq!(stats.failed(), 1);
+    }
+
+    #[test]
+    fn cancelled_searches_dont_run() {
+        let (stats, cache) = (Arc::new(SharedStats::new()), Arc::new(PrimeCache::new()));
+        let token = CancellationToken::new();
+        token.cancel();
+        let result = block_on(prime_output_instrumented(1, 10, stats.clone(), cache.clone(), token));
+        assert!(matches!(result, Err(SearchError::Cancelled)));
+        assert_eq!((stats.requests(), stats.cancelled(), stats.computed()), (1, 1, 0));
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+//! Async I/O carrying on while the blocking threads are busy.
+//!
+//! This is the whole point of handing CPU-heavy work to `blocking`: the search ties up a blocking
+//! thread, but the runtime's core thread is free, so network requests and the like keep moving.
+
+use crate::{spawn_with_handle, try_prime_output, PrimeResult};
+use futures::future::join;
+use std::io;
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Split a `http://host:port/path` URL into the address to connect to, the host name, and the
+/// path. The port defaults to 80 and the path to `/`.
+fn parse_http_url(url: &str) -> io::Result<(String, &str, &str)> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Not an http:// URL: {}", url)))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let host = authority.split(':').next().unwrap_or(authority);
+    let addr = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+    Ok((addr, host, path))
+}
+
+/// Fetch `url` with a bare-bones HTTP/1.0 GET, and return the response body. There's no support
+/// for HTTPS, redirects, or anything else fancy.
+pub async fn http_get(url: &str) -> io::Result<String> {
+    let (addr, host, path) = parse_http_url(url)?;
+    let mut stream = TcpStream::connect(addr.as_str()).await?;
+    let request = format!("GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n", path, host);
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let response = String::from_utf8_lossy(&response);
+    let body = response.split("\r\n\r\n").nth(1).unwrap_or_default();
+    Ok(body.to_string())
+}
+
+/// Search for the `n`th prime on a blocking thread while fetching `url`, and print each result the
+/// moment it arrives. The search is the slow [crate::find_nth_prime] one, so that there's plenty of
+/// time to see the response get printed as soon as it's in.
+pub async fn interleaved_io_and_compute(n: u64, url: &str) -> (PrimeResult, io::Result<String>) {
+    let t = Instant::now();
+    let compute = async {
+        let result = spawn_with_handle(try_prime_output(0, n)).await.expect("Couldn't block");
+        println!("[{:6.3}s] {}", t.elapsed().as_secs_f64(), result);
+        result
+    };
+    let fetch = async {
+        let body = http_get(url).await;
+        match &body {
+            Ok(body) => println!("[{:6.3}s] {} responded with {:?}", t.elapsed().as_secs_f64(), url, body),
+            Err(e) => println!("[{:6.3}s] Couldn't fetch {}: {}", t.elapsed().as_secs_f64(), url, e),
+        }
+        body
+    };
+    join(compute, fetch).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn response_arrives_while_the_search_runs() {
+        crate::prime_test! {
+            let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/", listener.local_addr().unwrap());
+            let server = spawn_with_handle(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buf).await.unwrap();
+                    assert!(read > 0, "The client hung up before finishing its request");
+                    request.extend_from_slice(&buf[..read]);
+                }
+                stream.write_all(b"HTTP/1.0 200 OK\r\n\r\nhello").await.unwrap();
+                Instant::now()
+            });
+            let (result, body) = interleaved_io_and_compute(50_000, &url).await;
+            let served_at = server.await;
+            assert_eq!(result.value, 611_953);
+            assert_eq!(body.unwrap(), "hello");
+            assert!(served_at < result.started_at + result.elapsed, "The response waited for the search");
+        }
+    }
+
+    #[test]
+    fn only_http_urls_are_supported() {
+        let default = ("example.com:80".to_string(), "example.com", "/");
+        assert_eq!(parse_http_url("http://example.com").unwrap(), default);
+        let explicit = ("localhost:8080".to_string(), "localhost", "/a/b");
+        assert_eq!(parse_http_url("http://localhost:8080/a/b").unwrap(), explicit);
+        assert!(parse_http_url("https://example.com").is_err());
+    }
+}
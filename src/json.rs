@@ -0,0 +1,124 @@
+//! Converting results to and from JSON by hand.
+//!
+//! A [PrimeResult] becomes a JSON object with the fields `"id"`, `"n"`, `"prime"`,
+//! `"elapsed_secs"` and `"peak_memory"`. Writing the conversions out, rather than deriving them
+//! with `serde`, keeps the field names in one place where they're easy to change, and leaves
+//! `PrimeResult` free of serialization attributes.
+//!
+//! `started_at` is an `Instant`, which only means something inside the process that made it, so
+//! it isn't written out. Reading a result back in sets it to the time it was read.
+
+use crate::PrimeResult;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Why a JSON value couldn't be turned into a [PrimeResult].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionError {
+    /// The field that was wrong, or `None` if the value wasn't an object at all
+    pub field: Option<&'static str>,
+    /// What was there instead, or `None` if the field was missing
+    pub found: Option<Value>,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.field, &self.found) {
+            (None, found) => {
+                write!(f, "A PrimeResult should be a JSON object, not {}", found.as_ref().unwrap_or(&Value::Null))
+            }
+            (Some(field), None) => write!(f, "The PrimeResult has no \"{}\" field", field),
+            (Some("elapsed_secs"), Some(found)) => {
+                write!(f, "\"elapsed_secs\" should be a non-negative number of seconds, not {}", found)
+            }
+            (Some(field), Some(found)) => write!(f, "\"{}\" should be a non-negative integer, not {}", field, found),
+        }
+    }
+}
+
+impl Error for ConversionError {}
+
+impl From<PrimeResult> for Value {
+    fn from(result: PrimeResult) -> Self {
+        json!({
+            "id": result.id,
+            "n": result.n,
+            "prime": result.value,
+            "elapsed_secs": result.elapsed.as_secs_f64(),
+            "peak_memory": result.peak_memory,
+        })
+    }
+}
+
+impl TryFrom<Value> for PrimeResult {
+    type Error = ConversionError;
+
+    /// Every field is needed except `"peak_memory"`, which is 0 if it's missing.
+    fn try_from(value: Value) -> Result<Self, ConversionError> {
+        let object = match value {
+            Value::Object(object) => object,
+            other => return Err(ConversionError { field: None, found: Some(other) }),
+        };
+        let field = |name: &'static str| object.get(name).ok_or(ConversionError { field: Some(name), found: None });
+        let wrong = |name, found: &Value| ConversionError { field: Some(name), found: Some(found.clone()) };
+        let integer = |name: &'static str| field(name).and_then(|v| v.as_u64().ok_or_else(|| wrong(name, v)));
+        let secs = field("elapsed_secs")?;
+        let elapsed = secs.as_f64().and_then(|s| Duration::try_from_secs_f64(s).ok());
+        let elapsed = elapsed.ok_or_else(|| wrong("elapsed_secs", secs))?;
+        let peak_memory = match object.get("peak_memory") {
+            None => 0,
+            Some(v) => v.as_u64().and_then(|m| usize::try_from(m).ok()).ok_or_else(|| wrong("peak_memory", v))?,
+        };
+        Ok(PrimeResult {
+            id: integer("id")?,
+            n: integer("n")?,
+            value: integer("prime")?,
+            started_at: Instant::now(),
+            elapsed,
+            peak_memory,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result() -> PrimeResult {
+        PrimeResult {
+            id: 3,
+            n: 10_000,
+            value: 104_729,
+            started_at: Instant::now(),
+            elapsed: Duration::from_micros(1_234_567),
+            peak_memory: 4096,
+        }
+    }
+
+    #[test]
+    fn round_trips_through_a_value() {
+        let original = result();
+        let value = Value::from(original.clone());
+        assert_eq!(value["prime"], 104_729);
+        let back = PrimeResult::try_from(value).unwrap();
+        // started_at isn't written out, so it can't come back
+        assert_eq!(PrimeResult { started_at: original.started_at, ..back }, original);
+    }
+
+    #[test]
+    fn missing_or_mistyped_fields_are_errors() {
+        let mut value = Value::from(result());
+        value.as_object_mut().unwrap().remove("prime");
+        let error = PrimeResult::try_from(value).unwrap_err();
+        assert_eq!(error, ConversionError { field: Some("prime"), found: None });
+        assert_eq!(error.to_string(), "The PrimeResult has no \"prime\" field");
+
+        let mut value = Value::from(result());
+        value["n"] = json!("many");
+        assert_eq!(PrimeResult::try_from(value).unwrap_err().field, Some("n"));
+        assert_eq!(PrimeResult::try_from(json!([1, 2])).unwrap_err().field, None);
+    }
+}
@@ -0,0 +1,102 @@
+//! The sieve of Eratosthenes, one prime at a time, with no limit given up front.
+//!
+//! [crate::sieve::sieve_primes] needs to know how far to go before it starts, and crosses off
+//! every multiple of every prime in one go. Melissa O'Neill's "genuine sieve of Eratosthenes"
+//! (from her paper on the famous Haskell one-liner, which isn't really a sieve at all) does the
+//! crossing off lazily instead: each prime found so far waits in a table at its next multiple,
+//! and only moves on when the sieve gets there.
+
+use crate::stream::spawn_chunk_producer;
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::HashMap;
+
+/// How many primes [lazy_sieve_stream] finds on each trip to a blocking thread.
+pub const LAZY_SIEVE_BATCH: usize = 1000;
+
+/// The primes in order, from 2, by O'Neill's lazy sieve.
+///
+/// `composites` maps each of the next composite numbers to the primes that divide it. When the
+/// sieve reaches a number that isn't in the map, it's prime, and it goes in at its square (any
+/// smaller multiple has a smaller prime factor, which will cross it off). When it reaches one that
+/// is, each of its primes moves on to its next multiple. So the map only ever holds one entry per
+/// prime found, rather than the whole range.
+#[derive(Debug, Clone)]
+pub struct LazyPrimeSieve {
+    composites: HashMap<u64, Vec<u64>>,
+    /// The next number to look at, or `None` once we've run past `u64::MAX`
+    current: Option<u64>,
+}
+
+impl Default for LazyPrimeSieve {
+    fn default() -> Self {
+        LazyPrimeSieve { composites: HashMap::new(), current: Some(2) }
+    }
+}
+
+impl LazyPrimeSieve {
+    pub fn new() -> Self {
+        LazyPrimeSieve::default()
+    }
+}
+
+impl Iterator for LazyPrimeSieve {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        while let Some(n) = self.current {
+            self.current = n.checked_add(1);
+            match self.composites.remove(&n) {
+                None => {
+                    // Primes past 2^32 have squares too big to ever reach
+                    if let Some(square) = n.checked_mul(n) {
+                        self.composites.insert(square, vec![n]);
+                    }
+                    return Some(n);
+                }
+                Some(primes) => {
+                    for p in primes {
+                        if let Some(next) = n.checked_add(p) {
+                            self.composites.entry(next).or_default().push(p);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Stream the primes from 2, found by a [LazyPrimeSieve] on a blocking thread [LAZY_SIEVE_BATCH]
+/// at a time. The sieve stops once the stream is dropped. This spawns the background task, so it
+/// has to be called from inside the runtime.
+pub async fn lazy_sieve_stream() -> impl Stream<Item = u64> {
+    let mut sieve = LazyPrimeSieve::new();
+    let batches = spawn_chunk_producer(move || {
+        let batch = sieve.by_ref().take(LAZY_SIEVE_BATCH).collect::<Vec<_>>();
+        if batch.is_empty() {
+            None
+        } else {
+            Some(batch)
+        }
+    });
+    batches.map(stream::iter).flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::first_primes;
+    use crate::tables::OEIS_A000040_FIRST_1000;
+
+    #[test]
+    fn first_100_are_the_first_100_primes() {
+        assert_eq!(LazyPrimeSieve::new().take(100).collect::<Vec<_>>(), OEIS_A000040_FIRST_1000[..100]);
+    }
+
+    #[test]
+    fn stream_carries_on_across_batches() {
+        let count = LAZY_SIEVE_BATCH * 2 + 500;
+        let streamed = crate::prime_test! { lazy_sieve_stream().await.take(count as u64).collect::<Vec<_>>().await };
+        assert_eq!(streamed, first_primes(count));
+    }
+}
@@ -0,0 +1,275 @@
+//! Examples of running CPU-heavy work alongside async code with Tokio's `blocking` threads.
+//!
+//! The demo binary in `main.rs` is the place to start. This library holds the pieces it's built
+//! from so that they can be reused, tested and benchmarked on their own.
+//!
+//! If you just want a batch of searches run, [prime_output_batch] is the place to start. It takes
+//! care of spawning, blocking threads and how many searches run at once, so all you need is a
+//! runtime:
+//!
+//! ```no_run
+//! use async_await::prime_output_batch;
+//!
+//! let rt = tokio::runtime::Runtime::new().unwrap();
+//! // The 1000th, 2000th and 3000th primes, two searches at a time
+//! let results = rt.block_on(prime_output_batch(vec![(0, 1000), (1, 2000), (2, 3000)], 2));
+//! for result in results {
+//!     println!("{}", result);
+//! }
+//! ```
+
+extern crate tokio_executor;
+
+pub mod analytic;
+pub mod approx;
+pub mod blocking_scope;
+pub mod budget;
+pub mod cache;
+pub mod cancellation;
+pub mod channel_comparison;
+pub mod collatz;
+pub mod collector;
+pub mod cpu_affinity;
+pub mod dag;
+pub mod digits;
+pub mod estimation;
+pub mod events;
+pub mod instrumented;
+pub mod interleaved;
+pub mod json;
+pub mod lazy_prime_sieve;
+pub mod math_utils;
+pub mod memory;
+pub mod metrics;
+pub mod networked;
+pub mod number_theory;
+pub mod output;
+pub mod parallel_search;
+pub mod pool;
+pub mod pool_sizing;
+pub mod prewarmed;
+pub mod primality;
+pub mod progress;
+pub mod race;
+pub mod resilient;
+pub mod scheduler;
+pub mod sieve;
+pub mod sieve_cache;
+pub mod sla;
+pub mod stats;
+pub mod stream;
+pub mod stress;
+pub mod tables;
+#[cfg(any(test, feature = "test-helpers"))]
+pub mod test_vectors;
+pub mod traced;
+pub mod two_phase;
+pub mod u128_primes;
+#[cfg(any(test, feature = "test-helpers"))]
+#[macro_use]
+pub mod testing;
+pub mod verification;
+pub mod visualization;
+
+pub use crate::cancellation::CancellationToken;
+pub use crate::dag::TaskGraph;
+pub use crate::pool::ComputePool;
+pub use crate::sieve::Sieve;
+
+use crate::memory::MemoryTracker;
+use tokio_executor::threadpool::{blocking, BlockingError};
+use futures::future::{join_all, poll_fn, FutureExt, RemoteHandle};
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// A really slow inefficient function for finding out if a value is prime
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    if n == 2 {
+        return true;
+    }
+    let n_sqrt = f64::sqrt(n as f64);
+    let n_sqrt = n_sqrt.trunc() as u64;
+    (2..=n_sqrt).all(|v| !n.is_multiple_of(v))
+}
+
+/// An even more inefficient prime finding algorithm. It does at least look the first 10000 primes
+/// up in [tables], and remember what it found before (see [cache]), so nearby searches on the same
+/// thread don't start from scratch.
+pub fn find_nth_prime(n: u64) -> u64 {
+    find_nth_prime_interruptible(n, || false).expect("An uninterruptible search was interrupted")
+}
+
+/// How many candidates [find_nth_prime_interruptible] tests between checks for cancellation.
+pub const CANCELLATION_CHECK_INTERVAL: u64 = 100_000;
+
+/// The same search as [find_nth_prime], but every [CANCELLATION_CHECK_INTERVAL] candidates it
+/// calls `cancelled`, and gives up with `None` if it returns `true`.
+///
+/// Nothing can interrupt a closure running on a blocking thread from the outside, so this is how a
+/// long search gets to bail out early: by checking in every now and then.
+pub fn find_nth_prime_interruptible<F: FnMut() -> bool>(n: u64, mut cancelled: F) -> Option<u64> {
+    if let Some(p) = tables::kth_prime_table(n) {
+        return Some(p);
+    }
+    let (mut found_primes, mut candidate) = cache::thread_cache_lookup(n).unwrap_or((0, 1));
+    while found_primes < n {
+        candidate += 1;
+        if candidate % CANCELLATION_CHECK_INTERVAL == 0 && cancelled() {
+            return None;
+        }
+        if is_prime(candidate) {
+            found_primes += 1;
+        }
+    }
+    if n > 0 {
+        cache::thread_cache_insert(n, candidate);
+    }
+    Some(candidate)
+}
+
+/// The outcome of a single prime search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrimeResult {
+    /// The task number
+    pub id: u64,
+    /// Which prime we went looking for
+    pub n: u64,
+    /// The `n`th prime
+    pub value: u64,
+    /// When the search started running on its blocking thread
+    pub started_at: Instant,
+    /// How long the search took on its blocking thread
+    pub elapsed: Duration,
+    /// The most heap the search had allocated at any one time, in bytes
+    pub peak_memory: usize,
+}
+
+impl fmt::Display for PrimeResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let elapsed = output::format_duration_human(self.elapsed);
+        write!(f, "#{:2}, {:6}th prime = {:12} ({:>7})", self.id, self.n, self.value, elapsed)
+    }
+}
+
+/// Run `search(n)` on this thread, timing it and tracking the memory it uses.
+pub(crate) fn time_search(id: u64, n: u64, search: impl FnOnce(u64) -> u64) -> PrimeResult {
+    try_time_search(id, n, |n| Some(search(n))).expect("The search always finishes")
+}
+
+/// The same as [time_search], for a search that can give up part way through by returning `None`.
+pub(crate) fn try_time_search(id: u64, n: u64, search: impl FnOnce(u64) -> Option<u64>) -> Option<PrimeResult> {
+    let tracker = MemoryTracker::start();
+    let t = Instant::now();
+    let value = search(n)?;
+    let elapsed = t.elapsed();
+    Some(PrimeResult { id, n, value, started_at: t, elapsed, peak_memory: tracker.peak_usage() })
+}
+
+/// Run `search(n)` on a blocking thread, timing it and tracking the memory it uses. How long it
+/// waited for the blocking thread goes in [metrics::wait_times].
+async fn timed_search(id: u64, n: u64, search: fn(u64) -> u64) -> Result<PrimeResult, BlockingError> {
+    let submitted = Instant::now();
+    poll_fn(move |_| {
+        blocking(|| {
+            metrics::wait_times().record(submitted.elapsed());
+            time_search(id, n, search)
+        })
+    }).await
+}
+
+/// Search for the `n`th prime with [find_nth_prime] on a blocking thread.
+///
+/// `blocking` only works on a thread pool worker thread, so this future has to be `spawn`ed onto
+/// the runtime rather than run directly with `block_on`. If it isn't, you get a `BlockingError`.
+pub async fn try_prime_output(id: u64, n: u64) -> Result<PrimeResult, BlockingError> {
+    timed_search(id, n, find_nth_prime).await
+}
+
+/// Search for the `n`th prime on a blocking thread, with the algorithm
+/// [primality::find_nth_prime_auto] picks for `n`. Unlike [try_prime_output], this is quick even
+/// for the demo's millions of primes.
+pub async fn prime_output(id: u64, n: u64) -> PrimeResult {
+    // So what's happening here?
+    // `timed_search` is a `Output=Result<PrimeResult, BlockingError>` future. The simplest way to
+    // get at the result is to `await` it and then handle the error. In this demo, we just panic if
+    // `blocking` returns an error.
+    timed_search(id, n, primality::find_nth_prime_with_sieve_fallback).await.expect("Couldn't block")
+}
+
+/// Spawn `future` onto the runtime, and hand back a handle that resolves to its output.
+///
+/// We can only `spawn` futures with `Output=()`. `remote_handle` splits a future into a `()` task
+/// that we can spawn, and a handle that resolves to the task's output.
+pub fn spawn_with_handle<F>(future: F) -> RemoteHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send,
+{
+    let (task, handle) = future.remote_handle();
+    tokio::spawn(task);
+    handle
+}
+
+/// Spawn [prime_output] onto the runtime, and hand back a handle that resolves to its result.
+pub fn spawn_prime_output(id: u64, n: u64) -> RemoteHandle<PrimeResult> {
+    spawn_with_handle(prime_output(id, n))
+}
+
+/// Run a search for each `(id, n)` in `pairs`, no more than `max_concurrent` at a time, and
+/// return all the results sorted by `id`.
+///
+/// This is the simplest way to run a batch of searches. Each one gets a blocking thread via
+/// [prime_output], and a [ComputePool] holds the rest back until there's room, so you don't need
+/// to `spawn` anything yourself: the future can go straight to `block_on`. A `max_concurrent` of 0
+/// is treated as 1.
+///
+/// There's no point in `max_concurrent` being much more than the runtime's blocking threads, since
+/// searches beyond that just queue for a thread, holding on to their memory while they wait. The
+/// results only come back once every search is done; to start on them sooner, use
+/// [stream::sorted_prime_stream], which yields each one as soon as every smaller id is done.
+pub async fn prime_output_batch(pairs: Vec<(u64, u64)>, max_concurrent: usize) -> Vec<PrimeResult> {
+    let mut pool = ComputePool::prime_pool(max_concurrent);
+    for (id, n) in pairs {
+        pool.submit_prime(id, n).await;
+    }
+    let mut results = pool.drain_results().await;
+    results.sort_by_key(|r| r.id);
+    results
+}
+
+/// Which searches [run_demo] runs: `count` of them, for the `max`th prime, then `step` fewer
+/// primes each time after that.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DemoConfig {
+    pub max: u64,
+    pub count: usize,
+    pub step: u64,
+}
+
+impl Default for DemoConfig {
+    /// The demo's 20 searches, from the 5,000,000th prime down to the 1,200,000th.
+    fn default() -> Self {
+        DemoConfig { max: 5_000_000, count: 20, step: 200_000 }
+    }
+}
+
+impl DemoConfig {
+    /// The `(id, n)` searches to run, from the hardest to find down to the easiest. `step` times
+    /// `count - 1` needs to be less than `max`, or the last few searches will be for the 0th prime,
+    /// which doesn't exist.
+    pub fn tasks(&self) -> Vec<(u64, u64)> {
+        (0..self.count as u64).map(|i| (i, self.max.saturating_sub(self.step * i))).collect()
+    }
+}
+
+/// Spawn every search in `config` at once, and hand back all the results, in the same order as
+/// [DemoConfig::tasks], once they're done. This is the demo in `main.rs` without the printing, so
+/// it has to run inside the thread pool runtime.
+pub fn run_demo(config: DemoConfig) -> impl Future<Output = Vec<PrimeResult>> {
+    let handles = config.tasks().into_iter().map(|(id, n)| spawn_prime_output(id, n)).collect::<Vec<_>>();
+    join_all(handles)
+}
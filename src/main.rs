@@ -1,65 +1,67 @@
-extern crate tokio_executor;
+mod cli;
 
-use tokio;
-use tokio_executor::threadpool::{blocking};
-use futures::future::poll_fn;
+use crate::cli::Options;
+use async_await::output::{self, TableAlign};
+use async_await::pool::ComputePool;
+use async_await::progress::AnsiSpinner;
+use async_await::stress::{self, StressConfig};
+use async_await::{cpu_affinity, memory, number_theory, prime_output, spawn_with_handle, stream};
+use async_await::{verification, visualization};
+use async_await::{DemoConfig, PrimeResult};
+use futures::future::{join_all, FutureExt};
 use std::time::Instant;
 
-/// A really slow inefficient function for finding out if a value is prime
-fn is_prime(n: u64) -> bool {
-    if n < 2 {
-        return false;
-    }
-    if n == 2 {
-        return true;
-    }
-    let n_sqrt = f64::sqrt(n as f64);
-    let n_sqrt = n_sqrt.trunc() as u64;
-    (2..=n_sqrt).all(|v| n % v != 0)
+/// The 20 `(id, n)` prime searches the demo runs, from the hardest to find down to the easiest.
+fn demo_tasks() -> Vec<(u64, u64)> {
+    DemoConfig::default().tasks()
 }
 
-/// An even more inefficient prime finding algorithm
-fn find_nth_prime(n: u64) -> u64 {
-    let mut found_primes = 0u64;
-    let mut candidate = 1u64;
-    while found_primes < n {
-        candidate += 1;
-        if is_prime(candidate) {
-            found_primes += 1;
-        }
+/// Spawn a search for 20 prime numbers starting with the hardest to find and running down to the
+/// easiest to find.
+async fn main_fut() {
+    for (id, n) in demo_tasks() {
+        // We want to `spawn` the search. This means it must be a task, which means the future must
+        // have `Output=()`. So we print the result here and throw it away.
+        tokio::spawn(async move { println!("{}", prime_output(id, n).await) });
     }
-    candidate
 }
 
-async fn prime_output(id: u64, n: u64) {
-    // So what's happening here?
-    // We want to `spawn` the result of this function. This means it must be a task, which means the
-    // future must have `Output=()`.
-    // But `poll_fn(blocking(..))` is a `Output=Result<(), BlockingError>` future. The simplest way
-    // to convert this to a task is to `await` the result (returning `Result<..>`) and then
-    // handling the error. In this demo, we just panic if `blocking` returns an error.
-    poll_fn(move |_| {
-        blocking(|| {
-            let t = Instant::now();
-            let val = find_nth_prime(n);
-            let t = t.elapsed();
-            println!("#{:2}, {:6}th prime = {:12} ({:6.3}s)", id, n, val, t.as_secs_f64());
-        })
-    }).await.expect("Couldn't block");
-
+/// The same searches as [main_fut], but hands back all the results once every search is done.
+/// If `print_as_found` is set, results are still printed as they come in.
+async fn main_fut_joined(print_as_found: bool) -> Vec<PrimeResult> {
+    let handles = demo_tasks().into_iter().map(|(id, n)| {
+        // A `RemoteHandle` is how we get the output of a spawned task back. `remote_handle` splits
+        // the future into a `()` task that we can spawn, and a handle that resolves to its output.
+        let (task, handle) = async move {
+            let result = prime_output(id, n).await;
+            if print_as_found {
+                println!("{}", result);
+            }
+            result
+        }.remote_handle();
+        tokio::spawn(task);
+        handle
+    }).collect::<Vec<_>>();
+    join_all(handles).await
 }
 
-/// Spawn a search for 20 prime numbers starting with the hardest to find and running down to the
-/// easiest to find.
-async fn main_fut() {
-    let max = 5_000_000u64;
-    for i in 0..20 {
-        let n = max - 200_000*i;
-        tokio::spawn(prime_output(i,n));
+/// The same searches again, but through a [ComputePool] that only lets 5 run at a time. We collect
+/// the results every 5 submissions, so each batch prints in submission order.
+async fn main_fut_pool() {
+    let mut pool = ComputePool::prime_pool(5);
+    for (id, n) in demo_tasks() {
+        pool.submit_prime(id, n).await;
+        if pool.pending_count() == 5 {
+            for result in pool.drain_results().await {
+                println!("{}", result);
+            }
+        }
+    }
+    for result in pool.drain_results().await {
+        println!("{}", result);
     }
 }
 
-
 /// Run a search for 20 prime numbers on 5 "blocking" threads. Since we start with the really hard
 /// to find primes, we expect the threads to return in reverse order. But there are only 5 threads
 /// looking for primes, so we expect #4 to return first, then #3 and so on. Once #4 is done, it'll
@@ -96,14 +98,66 @@ async fn main_fut() {
 /// #19, 1200000th prime =     18815231 (35.867s)      2	  638.177
 /// Bye
 /// ```
+///
+/// That run used the trial division in `find_nth_prime` for every search. `prime_output` now picks
+/// a much faster algorithm for each search (see `find_nth_prime_auto`), so the whole run takes
+/// seconds rather than minutes, and with searches that quick the finishing order is much less
+/// predictable.
+// The table above keeps the tabs it was pasted in with
+#[allow(clippy::tabs_in_doc_comments)]
 fn main()  {
-    let rt = tokio::runtime::Builder::new()
+    let options = Options::from_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let mut builder = tokio::runtime::Builder::new();
+    builder
         .blocking_threads(5)
         // Run the work scheduler on one thread so we can really see the effects of using `blocking` above
-        .core_threads(1)
-        .build()
-        .expect("Could not create runtime");
-    rt.block_on(main_fut());
+        .core_threads(1);
+    if options.cpu_affinity {
+        cpu_affinity::pin_threads_to_cores(&mut builder);
+    }
+    let rt = builder.build().expect("Could not create runtime");
+    if let Some(size) = options.spiral {
+        rt.block_on(async move { spawn_with_handle(visualization::print_spiral_blocking(size)).await });
+    } else if options.pi_vs_li {
+        number_theory::print_prime_number_race(10_000_000);
+    } else if options.stress_test {
+        println!("{}", rt.block_on(stress::stress_test_blocking_pool(StressConfig::default())));
+    } else if options.pool {
+        rt.block_on(main_fut_pool());
+    } else if options.compare_orderings {
+        rt.block_on(stream::compare_orderings(demo_tasks()));
+    } else if options.needs_results() {
+        let wall_start = Instant::now();
+        let results = if options.spinner {
+            // Results printed as they come in would land on the spinner's line, so hold them back
+            // and print them all at the end instead
+            let searches = main_fut_joined(false);
+            let results = rt.block_on(AnsiSpinner::default().spin_until("Searching for primes", searches));
+            print!("{}", output::format_prime_table(&results, TableAlign::Right));
+            results
+        } else {
+            rt.block_on(main_fut_joined(true))
+        };
+        if options.track_memory {
+            memory::print_memory_table(&results);
+        }
+        if options.verify {
+            verification::print_verification(&results);
+            println!("{}", verification::verify_against_known_primes(&results));
+        }
+        if options.histogram {
+            let largest = results.iter().map(|r| r.value).max().unwrap_or(0);
+            print!("{}", visualization::prime_gap_histogram(2, largest, 20));
+        }
+        if options.timeline {
+            visualization::print_task_timeline(&results, wall_start, 60);
+        }
+    } else {
+        rt.block_on(main_fut());
+    }
     rt.shutdown_on_idle();
     println!("Bye");
 }
@@ -0,0 +1,47 @@
+//! Modular arithmetic on `u64`s, shared by the primality tests and the number theory.
+
+/// `a * b mod modulus`. The product is worked out in a `u128`, so it can't overflow. Panics if
+/// `modulus` is 0.
+pub fn modular_multiplication(a: u64, b: u64, modulus: u64) -> u64 {
+    ((a as u128 * b as u128) % modulus as u128) as u64
+}
+
+/// `base^exp mod modulus`, by repeated squaring, so it takes `O(log exp)` multiplications. `0^0`
+/// is 1, and anything mod 1 is 0. Panics if `modulus` is 0.
+pub fn modular_exponentiation(mut base: u64, mut exp: u64, modulus: u64) -> u64 {
+    let mut result = 1 % modulus;
+    base %= modulus;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = modular_multiplication(result, base, modulus);
+        }
+        base = modular_multiplication(base, base, modulus);
+        exp >>= 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_powers() {
+        assert_eq!(modular_exponentiation(2, 10, 1000), 24);
+        assert_eq!(modular_exponentiation(3, 0, 7), 1);
+        assert_eq!(modular_exponentiation(0, 5, 7), 0);
+        assert_eq!(modular_exponentiation(0, 0, 7), 1);
+        assert_eq!(modular_exponentiation(5, 3, 1), 0);
+    }
+
+    #[test]
+    fn big_operands_dont_overflow() {
+        let max = u64::MAX;
+        assert_eq!(modular_multiplication(max - 1, max - 1, max), 1);
+        assert_eq!(modular_multiplication(1 << 63, 4, max), 2);
+        // Fermat's little theorem, for the Mersenne prime 2^61 - 1
+        let p = (1 << 61) - 1;
+        assert_eq!(modular_exponentiation(max, p - 1, p), 1);
+        assert_eq!(modular_exponentiation(3, p, p), 3);
+    }
+}
@@ -0,0 +1,112 @@
+//! Heap usage tracking, courtesy of jemalloc.
+//!
+//! jemalloc keeps a running tally of allocations that we can query cheaply. The global statistics
+//! are cached and only refreshed when the "epoch" is advanced, so [snapshot_allocated] bumps the
+//! epoch before reading. jemalloc also tracks a per-thread high-water mark, which is exactly what
+//! we want for a search running on a single blocking thread.
+
+use crate::PrimeResult;
+use std::ptr;
+use tikv_jemalloc_ctl::{epoch, raw, stats};
+use tikv_jemallocator::Jemalloc;
+
+// None of the statistics mean anything unless jemalloc is actually doing the allocating.
+#[global_allocator]
+static GLOBAL: Jemalloc = Jemalloc;
+
+/// The number of bytes currently allocated by the whole process.
+pub fn snapshot_allocated() -> usize {
+    epoch::advance().expect("Could not advance the jemalloc epoch");
+    stats::allocated::read().expect("Could not read jemalloc allocation stats")
+}
+
+/// Call `f`, and return its result along with how many more bytes the process has allocated
+/// afterwards than before.
+///
+/// That's what `f` left behind, including whatever it returns, not the most it used along the
+/// way: a sieve that fills a big table and returns a short list only counts the list. Use a
+/// [MemoryTracker] for the high-water mark. The count covers the whole process, so anything other
+/// threads allocate or free while `f` runs shows up too.
+pub fn measure_allocation<F, T>(f: F) -> (T, usize)
+where
+    F: FnOnce() -> T,
+{
+    let before = snapshot_allocated();
+    let result = f();
+    let after = snapshot_allocated();
+    (result, after.saturating_sub(before))
+}
+
+/// Records how much memory the current thread uses from the moment it is created.
+///
+/// A tracker only sees allocations made on the thread it was created on, so create it inside the
+/// `blocking` closure, not before handing the work off.
+pub struct MemoryTracker {
+    baseline: usize,
+}
+
+impl MemoryTracker {
+    /// Reset the current thread's high-water mark and start tracking.
+    pub fn start() -> Self {
+        // `thread.peak.reset` takes neither an old nor a new value, which the safe wrappers in
+        // `tikv_jemalloc_ctl` can't express, so call `mallctl` directly.
+        let res = unsafe {
+            tikv_jemalloc_sys::mallctl(
+                b"thread.peak.reset\0".as_ptr() as *const _,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                0,
+            )
+        };
+        assert_eq!(res, 0, "Could not reset the jemalloc thread peak");
+        MemoryTracker { baseline: snapshot_allocated() }
+    }
+
+    /// The process-wide allocation when tracking started.
+    pub fn baseline(&self) -> usize {
+        self.baseline
+    }
+
+    /// The most memory (in bytes) this thread has had allocated at any one time since the tracker
+    /// started. jemalloc only promises this is accurate to within about 100kB.
+    pub fn peak_usage(&self) -> usize {
+        let peak: u64 = unsafe { raw::read(b"thread.peak.read\0") }.expect("Could not read jemalloc thread peak");
+        peak as usize
+    }
+}
+
+/// Print the peak memory usage of each search in id order.
+pub fn print_memory_table(results: &[PrimeResult]) {
+    let mut results = results.iter().collect::<Vec<_>>();
+    results.sort_by_key(|r| r.id);
+    println!("  # {:>8} {:>18}", "n", "peak memory");
+    for r in results {
+        println!("#{:2} {:8} {:>12} bytes", r.id, r.n, r.peak_memory);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::sieve_primes;
+    use crate::spawn_prime_output;
+
+    #[test]
+    fn tracker_sees_a_sieve_allocate() {
+        let tracker = MemoryTracker::start();
+        let primes = sieve_primes(1_000_000);
+        assert_eq!(primes.len(), 78_498);
+        assert!(tracker.peak_usage() > 0);
+    }
+
+    #[test]
+    fn searches_record_their_peak() {
+        crate::prime_test! {
+            // Small enough to be sieved, which allocates, rather than tested one by one, which doesn't
+            let result = spawn_prime_output(0, 10_000).await;
+            assert_eq!(result.value, 104_729);
+            assert!(result.peak_memory > 0);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! How long searches spend queueing for a blocking thread.
+//!
+//! `blocking` only runs the search once there's a blocking thread free to run it. Until then the
+//! task just waits, so when the runtime is busy a search can spend longer waiting to start than it
+//! spends running. Every search started with [crate::prime_output] or [crate::try_prime_output]
+//! records how long that wait was in the global [wait_times] histogram.
+
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+const BUCKETS: usize = 64;
+
+/// Counts durations in buckets that double in size, from nanoseconds all the way up to centuries.
+///
+/// Recording a duration is a single atomic increment, so many threads can record into the same
+/// histogram at once without slowing each other down much. The price is precision: a percentile
+/// is only known to within a factor of two.
+pub struct Histogram {
+    /// Bucket 0 counts zero durations. Bucket `i` counts durations of `2^(i-1)` up to `2^i - 1`
+    /// nanoseconds.
+    buckets: [AtomicU64; BUCKETS],
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram::new()
+    }
+}
+
+impl Histogram {
+    pub const fn new() -> Self {
+        Histogram { buckets: [const { AtomicU64::new(0) }; BUCKETS] }
+    }
+
+    /// Count `d` in its bucket.
+    pub fn record(&self, d: Duration) {
+        let nanos = u64::try_from(d.as_nanos()).unwrap_or(u64::MAX);
+        let bucket = (u64::BITS - nanos.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// How many durations have been recorded.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).sum()
+    }
+
+    /// A duration that at least a fraction `p` of the recorded durations are no longer than,
+    /// rounded up to the top of its bucket. So `percentile(0.99)` is the p99. `p` is clamped to
+    /// between 0 and 1, and an empty histogram always gives zero.
+    pub fn percentile(&self, p: f64) -> Duration {
+        let counts = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect::<Vec<_>>();
+        let total = counts.iter().sum::<u64>();
+        if total == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((p.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, count) in counts.into_iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_nanos(bucket_max(i));
+            }
+        }
+        Duration::from_nanos(u64::MAX)
+    }
+
+    /// Forget everything recorded so far.
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The longest duration, in nanoseconds, counted in bucket `i`.
+fn bucket_max(i: usize) -> u64 {
+    match i {
+        0 => 0,
+        i if i >= BUCKETS - 1 => u64::MAX,
+        i => (1 << i) - 1,
+    }
+}
+
+/// The time every search has spent between being handed to `blocking` and starting to run.
+pub fn wait_times() -> &'static Histogram {
+    static WAIT_TIMES: Histogram = Histogram::new();
+    &WAIT_TIMES
+}
+
+/// The `p` percentile of the time searches have spent waiting for a blocking thread, e.g. `0.5`,
+/// `0.95` or `0.99`. See [Histogram::percentile].
+pub fn wait_time_percentile(p: f64) -> Duration {
+    wait_times().percentile(p)
+}
+
+/// Counters for a group of searches to share, so that one set of searches can be measured apart
+/// from everything else the process is doing. Everything is atomic, so they can all record into
+/// the same `Arc<SharedStats>` at once.
+#[derive(Default)]
+pub struct SharedStats {
+    requests: AtomicU64,
+    cache_hits: AtomicU64,
+    computed: AtomicU64,
+    cancelled: AtomicU64,
+    failed: AtomicU64,
+    compute_nanos: AtomicU64,
+    wait_times: Histogram,
+}
+
+impl SharedStats {
+    pub fn new() -> Self {
+        SharedStats::default()
+    }
+
+    /// Count a search being asked for, however it turns out.
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a search answered from a cache, without running it.
+    pub fn record_cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a search that ran on a blocking thread, after waiting `wait` for the thread, and then
+    /// taking `elapsed`.
+    pub fn record_computed(&self, wait: Duration, elapsed: Duration) {
+        self.computed.fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.compute_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.wait_times.record(wait);
+    }
+
+    /// Count a search that was cancelled before it ran.
+    pub fn record_cancelled(&self) {
+        self.cancelled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a search that couldn't get a blocking thread.
+    pub fn record_failed(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn computed(&self) -> u64 {
+        self.computed.load(Ordering::Relaxed)
+    }
+
+    pub fn cancelled(&self) -> u64 {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// The time all the computed searches spent running, added up.
+    pub fn compute_time(&self) -> Duration {
+        Duration::from_nanos(self.compute_nanos.load(Ordering::Relaxed))
+    }
+
+    /// How long the computed searches waited for a blocking thread.
+    pub fn wait_times(&self) -> &Histogram {
+        &self.wait_times
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn_prime_output;
+    use crate::testing::test_runtime_single_thread;
+    use futures::future::join_all;
+
+    #[test]
+    fn percentiles_round_up_to_their_bucket() {
+        let histogram = Histogram::new();
+        assert_eq!(histogram.percentile(0.5), Duration::ZERO);
+        for nanos in 1..=100 {
+            histogram.record(Duration::from_nanos(nanos));
+        }
+        assert_eq!(histogram.count(), 100);
+        // 50 is in the 32..=63 bucket, and 99 in the 64..=127 one
+        assert_eq!(histogram.percentile(0.5), Duration::from_nanos(63));
+        assert_eq!(histogram.percentile(0.99), Duration::from_nanos(127));
+        assert_eq!(histogram.percentile(2.0), Duration::from_nanos(127));
+        histogram.reset();
+        assert_eq!(histogram.count(), 0);
+    }
+
+    #[test]
+    fn searches_record_their_wait() {
+        let before = wait_times().count();
+        test_runtime_single_thread().block_on(async {
+            join_all((0..100).map(|id| spawn_prime_output(id, 10))).await;
+        });
+        // Other tests record into the same histogram, so there may be even more
+        assert!(wait_times().count() >= before + 100);
+        // With a single blocking thread, nearly every search had to queue
+        let p99 = wait_time_percentile(0.99);
+        assert!(p99 > Duration::ZERO);
+        assert!(p99 >= wait_time_percentile(0.5));
+    }
+}
@@ -0,0 +1,119 @@
+//! Spreading a search over workers on other machines.
+//!
+//! The protocol is as small as it can be. The coordinator opens a TCP connection to a worker,
+//! sends one request, `{"start": X, "count": Y}`, and shuts down its side of the connection. The
+//! worker finds the primes in `X..X+Y`, sends them back as `{"primes": [...]}`, and closes the
+//! connection. One connection, one segment.
+//!
+//! Each worker runs [prime_worker_server], and the coordinator runs [find_nth_prime_networked].
+//! Nothing here checks who's connecting, so only run a worker somewhere you trust everyone who
+//! can reach it.
+
+use crate::approx::{PrimeApproximator, PrimeNumberTheoremApprox};
+use crate::sieve::segmented_sieve;
+use futures::future::{join_all, poll_fn};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{Shutdown, SocketAddr};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_executor::threadpool::blocking;
+
+/// What the coordinator asks a worker for: the primes from `start` up to, but not including,
+/// `start + count`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SegmentRequest {
+    pub start: u64,
+    pub count: u64,
+}
+
+/// What a worker sends back: the primes in the segment it was asked for, in increasing order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SegmentResponse {
+    pub primes: Vec<u64>,
+}
+
+fn invalid_data(e: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Answer segment requests on `addr`, forever.
+///
+/// Each connection is handled in its own task, and each segment is sieved on a blocking thread,
+/// so this has to be `spawn`ed onto the runtime. A connection that sends something that isn't a
+/// request just gets closed. This only returns if it can't listen on `addr`, or stops being able
+/// to accept connections.
+pub async fn prime_worker_server(addr: SocketAddr) -> io::Result<()> {
+    serve_segment_requests(TcpListener::bind(addr).await?).await
+}
+
+/// The same as [prime_worker_server], on a listener that's already bound. Bind to port 0 and ask
+/// the listener for its `local_addr` to let the OS pick a free port.
+pub async fn serve_segment_requests(mut listener: TcpListener) -> io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            let _ = handle_segment_request(stream).await;
+        });
+    }
+}
+
+async fn handle_segment_request(mut stream: TcpStream) -> io::Result<()> {
+    let mut request = Vec::new();
+    stream.read_to_end(&mut request).await?;
+    let request: SegmentRequest = serde_json::from_slice(&request).map_err(invalid_data)?;
+    let high = request.start.saturating_add(request.count);
+    let primes = poll_fn(|_| blocking(|| segmented_sieve(request.start, high)))
+        .await
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let response = serde_json::to_vec(&SegmentResponse { primes }).map_err(invalid_data)?;
+    stream.write_all(&response).await?;
+    stream.shutdown(Shutdown::Write)
+}
+
+/// Ask the worker at `addr` for the primes in one segment.
+pub async fn request_segment(addr: SocketAddr, segment: SegmentRequest) -> io::Result<Vec<u64>> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let request = serde_json::to_vec(&segment).map_err(invalid_data)?;
+    stream.write_all(&request).await?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let response: SegmentResponse = serde_json::from_slice(&response).map_err(invalid_data)?;
+    Ok(response.primes)
+}
+
+/// Find the `n`th prime (counting from 1) with the workers listening at `worker_addrs`.
+///
+/// The `n`th prime is no more than [PrimeNumberTheoremApprox]'s estimate, so we split the numbers
+/// up to that into one segment per worker, ask every worker for its segment at once, and count
+/// through the answers in order. The segments are the same width, so the workers with the lower
+/// segments, which hold more primes, have a little more to send back.
+///
+/// Fails if there are no workers, any worker can't be reached or sends back nonsense, or `n` is 0.
+pub async fn try_find_nth_prime_networked(n: u64, worker_addrs: Vec<SocketAddr>) -> io::Result<u64> {
+    if n == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "There is no 0th prime"));
+    }
+    if worker_addrs.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "There are no workers to search with"));
+    }
+    let high = PrimeNumberTheoremApprox.nth_prime_approx(n) + 1;
+    let width = (high - 2).div_ceil(worker_addrs.len() as u64);
+    let requests = worker_addrs.iter().enumerate().map(|(i, &addr)| {
+        let start = 2 + i as u64 * width;
+        request_segment(addr, SegmentRequest { start, count: width.min(high.saturating_sub(start)) })
+    });
+    let mut primes = Vec::new();
+    for segment in join_all(requests).await {
+        primes.extend(segment?);
+    }
+    primes.get(n as usize - 1).copied().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "The workers sent back fewer primes than there should be")
+    })
+}
+
+/// [try_find_nth_prime_networked], but panics if the search fails.
+pub async fn find_nth_prime_networked(n: u64, worker_addrs: Vec<SocketAddr>) -> u64 {
+    try_find_nth_prime_networked(n, worker_addrs).await.expect("The networked search failed")
+}
@@ -0,0 +1,742 @@
+//! Questions about how the primes are spread out.
+
+use crate::cache::PrimalityCache;
+use crate::math_utils::{modular_exponentiation, modular_multiplication};
+use crate::primality::{is_prime_fast, miller_rabin_random, PrimeIterator};
+use crate::sieve::sieve_primes;
+use crate::u128_primes::is_prime_u128;
+use futures::channel::mpsc;
+use futures::future::poll_fn;
+use futures::sink::SinkExt;
+use futures::stream::{Stream, StreamExt};
+use rand::Rng;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use tokio_executor::threadpool::blocking;
+
+/// The first 20 maximal prime gaps, as `(p, q)` pairs of consecutive primes. Each gap `q - p` is
+/// bigger than every gap before it. These are the published values (OEIS A002386 and A000101).
+static KNOWN_MAXIMAL_PRIME_GAPS: [(u64, u64); 20] = [
+    (2, 3),
+    (3, 5),
+    (7, 11),
+    (23, 29),
+    (89, 97),
+    (113, 127),
+    (523, 541),
+    (887, 907),
+    (1129, 1151),
+    (1327, 1361),
+    (9551, 9587),
+    (15683, 15727),
+    (19609, 19661),
+    (31397, 31469),
+    (155_921, 156_007),
+    (360_653, 360_749),
+    (370_261, 370_373),
+    (492_113, 492_227),
+    (1_349_533, 1_349_651),
+    (1_357_201, 1_357_333),
+];
+
+/// The first `count` pairs of consecutive primes `(p, q)` with `q - p > threshold`, in order.
+///
+/// Gaps get bigger only very slowly (the first gap over 100 is after 370,261), so a big
+/// `threshold` means a long scan.
+pub fn prime_gaps_above(threshold: u64, count: usize) -> Vec<(u64, u64)> {
+    let primes = PrimeIterator::starting_from(2);
+    primes.clone().zip(primes.skip(1)).filter(|&(p, q)| q - p > threshold).take(count).collect()
+}
+
+/// The same as [prime_gaps_above], but the scan runs on a blocking thread, so this has to be
+/// `spawn`ed onto the runtime.
+pub async fn prime_gaps_above_blocking(threshold: u64, count: usize) -> Vec<(u64, u64)> {
+    poll_fn(|_| blocking(|| prime_gaps_above(threshold, count))).await.expect("Couldn't block")
+}
+
+/// The first 20 maximal prime gaps, from [KNOWN_MAXIMAL_PRIME_GAPS].
+pub fn known_maximal_prime_gaps() -> &'static [(u64, u64)] {
+    &KNOWN_MAXIMAL_PRIME_GAPS
+}
+
+/// The prime factors of `n`, smallest first, with each repeated as many times as it divides `n`.
+/// `1` and `0` have no prime factors.
+///
+/// This is trial division that only divides by primes, using `is_prime` to pick them out. Every
+/// factorization tests the same small divisors all over again, so it's a good fit for a
+/// [PrimalityCache]: pass one in as `cache` to share those tests between calls (and threads).
+pub fn prime_factorization(n: u64, cache: Option<&PrimalityCache>) -> Vec<u64> {
+    match cache {
+        Some(cache) => prime_factorization_using(n, |d| cache.is_prime_cached(d)),
+        None => prime_factorization_using(n, crate::is_prime),
+    }
+}
+
+/// The same as [prime_factorization], but with `is_prime` doing the primality tests.
+pub fn prime_factorization_using<F: FnMut(u64) -> bool>(mut n: u64, mut is_prime: F) -> Vec<u64> {
+    let mut factors = Vec::new();
+    let mut d = 2;
+    while n > 1 && d <= n / d {
+        if is_prime(d) {
+            while n.is_multiple_of(d) {
+                factors.push(d);
+                n /= d;
+            }
+        }
+        d += 1;
+    }
+    if n > 1 {
+        factors.push(n);
+    }
+    factors
+}
+
+/// [stream_prime_factors] tries dividing by everything up to this before switching to Pollard's
+/// rho.
+pub const TRIAL_DIVISION_LIMIT: u64 = 10_000;
+
+/// A factor of the composite `n` other than 1 and `n`, by Pollard's rho with Brent's cycle finding.
+///
+/// Each `x -> x² + c` sequence mod `n` eventually cycles, and it usually cycles much sooner mod one
+/// of `n`'s prime factors `p`, which shows up as a common factor of `n` and the difference between
+/// two terms. Now and then the sequence cycles mod every factor at once, and we try again with the
+/// next `c`.
+fn pollard_rho(n: u64) -> u64 {
+    if n.is_multiple_of(2) {
+        return 2;
+    }
+    for c in 1.. {
+        let f = |x: u64| ((modular_multiplication(x, x, n) as u128 + c as u128) % n as u128) as u64;
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        let mut power = 1;
+        let mut steps = 0;
+        while d == 1 {
+            if steps == power {
+                x = y;
+                power *= 2;
+                steps = 0;
+            }
+            y = f(y);
+            steps += 1;
+            d = gcd(x.abs_diff(y), n);
+        }
+        if d != n {
+            return d;
+        }
+    }
+    unreachable!("Ran out of values of c")
+}
+
+/// A prime factor of `n`, which must be more than 1. Not necessarily the smallest.
+fn some_prime_factor(mut n: u64) -> u64 {
+    while !is_prime_fast(n) {
+        n = pollard_rho(n);
+    }
+    n
+}
+
+/// Finds the prime factors of a number one at a time.
+///
+/// Every factor up to [TRIAL_DIVISION_LIMIT] comes first, smallest first. Whatever's left over
+/// has only big prime factors, and those come out in whatever order Pollard's rho finds them.
+#[derive(Debug, Clone)]
+pub struct PrimeFactors {
+    /// What's left to factorize
+    remaining: u64,
+    /// The next trial divisor
+    divisor: u64,
+}
+
+impl PrimeFactors {
+    pub fn new(n: u64) -> Self {
+        PrimeFactors { remaining: n, divisor: 2 }
+    }
+
+    /// Take every factor of `p` out of what's left, and return `(p, how many there were)`.
+    fn divide_out(&mut self, p: u64) -> (u64, u32) {
+        let mut exponent = 0;
+        while self.remaining.is_multiple_of(p) {
+            self.remaining /= p;
+            exponent += 1;
+        }
+        (p, exponent)
+    }
+}
+
+impl Iterator for PrimeFactors {
+    type Item = (u64, u32);
+
+    fn next(&mut self) -> Option<(u64, u32)> {
+        if self.remaining < 2 {
+            return None;
+        }
+        while self.divisor <= TRIAL_DIVISION_LIMIT && self.divisor <= self.remaining / self.divisor {
+            let d = self.divisor;
+            self.divisor += if d == 2 { 1 } else { 2 };
+            if self.remaining.is_multiple_of(d) {
+                return Some(self.divide_out(d));
+            }
+        }
+        let p = some_prime_factor(self.remaining);
+        Some(self.divide_out(p))
+    }
+}
+
+/// Stream each of `n`'s prime factors, as `(factor, exponent)`, as soon as it's found. The small
+/// factors come first, in order, then the large ones in the order they're found; see
+/// [PrimeFactors]. So `60` streams `(2, 2)`, `(3, 1)` then `(5, 1)`, and `0` and `1` stream
+/// nothing.
+///
+/// The factors are found one at a time on a blocking thread, by a task this spawns, so it has to
+/// be called from inside the runtime. Dropping the stream stops the search after its current
+/// factor.
+pub async fn stream_prime_factors(n: u64) -> impl Stream<Item = (u64, u32)> {
+    let (mut tx, rx) = mpsc::channel(1);
+    let mut factors = PrimeFactors::new(n);
+    tokio::spawn(async move {
+        while let Ok(Some(factor)) = poll_fn(|_| blocking(|| factors.next())).await {
+            if tx.send(factor).await.is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Print `n`'s prime factorization, a factor at a time as each is found, like
+/// `60 = 2^2 × 3 × 5`. Like [stream_prime_factors], this has to run inside the runtime.
+pub async fn print_factors_as_found(n: u64) {
+    print!("{} =", n);
+    io::stdout().flush().ok();
+    let mut factors = stream_prime_factors(n).await;
+    let mut first = true;
+    while let Some((p, exponent)) = factors.next().await {
+        print!("{}{}", if first { " " } else { " × " }, p);
+        if exponent > 1 {
+            print!("^{}", exponent);
+        }
+        io::stdout().flush().ok();
+        first = false;
+    }
+    println!();
+}
+
+/// The greatest common divisor of `a` and `b`, by Euclid's algorithm. `gcd(0, 0)` is 0.
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The extended Euclidean algorithm: `(x, y, gcd(a, b))`, with `a * x + b * y = gcd(a, b)`.
+///
+/// When `a` and `b` are different and both nonzero, these are the smallest such `x` and `y`: `|x|`
+/// is at most `b / (2 gcd)` and `|y|` at most `a / (2 gcd)`, which is how they fit in an `i64`.
+/// Otherwise they're just 0 and 1, so `extended_gcd(7, 0)` is `(1, 0, 7)` and `extended_gcd(7, 7)`
+/// is `(0, 1, 7)`. `extended_gcd(35, 15)` is `(1, -2, 5)`.
+pub fn extended_gcd(a: u64, b: u64) -> (i64, i64, u64) {
+    let (mut old_r, mut r) = (a as i128, b as i128);
+    let (mut old_x, mut x) = (1i128, 0i128);
+    let (mut old_y, mut y) = (0i128, 1i128);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_x, x) = (x, old_x - q * x);
+        (old_y, y) = (y, old_y - q * y);
+    }
+    (old_x as i64, old_y as i64, old_r as u64)
+}
+
+/// The `x` in `0..modulus` with `a * x ≡ 1 (mod modulus)`, or `None` if there isn't one, which is
+/// when `a` and `modulus` have a common factor. So `modular_inverse(3, 11)` is `Some(4)`, but
+/// there's no inverse of 4 mod 6. Everything is its own inverse mod 1, and there's none mod 0.
+pub fn modular_inverse(a: u64, modulus: u64) -> Option<u64> {
+    if modulus == 0 {
+        return None;
+    }
+    match extended_gcd(a % modulus, modulus) {
+        (x, _, 1) => Some((x as i128).rem_euclid(modulus as i128) as u64),
+        _ => None,
+    }
+}
+
+/// The smallest `x` with `x ≡ remainder (mod modulus)` for each of the `(remainder, modulus)`
+/// pairs, or `None` if there's no such `x`. An empty list is satisfied by 0.
+///
+/// With pairwise coprime moduli, the Chinese remainder theorem says there's always exactly one
+/// solution below their product. We combine the pairs one at a time, and the moduli don't have to
+/// be coprime: two pairs that agree on what `x` leaves mod their common factor combine into one mod
+/// their lowest common multiple, and two that don't can't both hold. We also give up with `None`
+/// if a modulus is 0, or the combined modulus outgrows a `u64`.
+pub fn chinese_remainder_theorem(remainders: &[(u64, u64)]) -> Option<u64> {
+    let (mut x, mut modulus) = (0u128, 1u128);
+    for &(r, m) in remainders {
+        if m == 0 {
+            return None;
+        }
+        let (r, m) = (r as u128 % m as u128, m as u128);
+        let g = gcd(modulus as u64, m as u64) as u128;
+        if (x % g) != (r % g) {
+            return None;
+        }
+        // x + k * modulus ≡ r (mod m), so k ≡ (r - x) / g * (modulus / g)^-1 (mod m / g)
+        let step = m / g;
+        let diff = (r + m - x % m) % m / g;
+        let inverse = modular_inverse((modulus / g % step) as u64, step as u64)? as u128;
+        let k = diff % step * inverse % step;
+        x += k * modulus;
+        modulus = u64::try_from(modulus * step).ok()? as u128;
+    }
+    Some(x as u64)
+}
+
+/// An arithmetic progression that can't hold more than one prime, because `start` and `diff` have
+/// a common factor (or `diff` is 0).
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidProgressionError {
+    pub start: u64,
+    pub diff: u64,
+}
+
+impl fmt::Display for InvalidProgressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} + k * {} has at most one prime, since gcd({}, {}) = {}",
+            self.start,
+            self.diff,
+            self.start,
+            self.diff,
+            gcd(self.start, self.diff)
+        )
+    }
+}
+
+impl Error for InvalidProgressionError {}
+
+/// The first `length` primes of the form `start + k * diff`, for `k` from 0 up, in order. So
+/// `find_prime_arithmetic_progressions(1, 6, 5)` is `[7, 13, 19, 31, 37]`.
+///
+/// Dirichlet's theorem says there are infinitely many such primes as long as `start` and `diff`
+/// have no common factor, so that's an error otherwise. There are only so many below `u64::MAX`,
+/// though: if the progression runs past it, you get the primes found up to then.
+pub fn find_prime_arithmetic_progressions(
+    start: u64,
+    diff: u64,
+    length: usize,
+) -> Result<Vec<u64>, InvalidProgressionError> {
+    if diff == 0 || gcd(start, diff) != 1 {
+        return Err(InvalidProgressionError { start, diff });
+    }
+    let terms = std::iter::successors(Some(start), |&t| t.checked_add(diff));
+    Ok(terms.filter(|&t| is_prime_fast(t)).take(length).collect())
+}
+
+/// The same as [find_prime_arithmetic_progressions], but the search runs on a blocking thread, so
+/// this has to be `spawn`ed onto the runtime.
+pub async fn prime_ap_blocking(start: u64, diff: u64, length: usize) -> Result<Vec<u64>, InvalidProgressionError> {
+    poll_fn(|_| blocking(|| find_prime_arithmetic_progressions(start, diff, length))).await.expect("Couldn't block")
+}
+
+/// The widest primes [generate_rsa_prime] makes. Two primes this wide multiply to a modulus that
+/// still fits in a `u64`.
+pub const RSA_MAX_BITS: u32 = 32;
+
+/// How many random bases [generate_rsa_prime] tests each candidate against.
+const RSA_MILLER_RABIN_ROUNDS: u32 = 20;
+
+/// A random prime exactly `bits` wide, i.e. with its top bit set. Panics unless `bits` is from 3
+/// to [RSA_MAX_BITS].
+///
+/// This picks random odd numbers of the right width until one passes [miller_rabin_random] with
+/// 20 bases, which, as in real RSA key generation, makes it a probable prime rather than a proven
+/// one. That's as far as the likeness goes, though: real RSA needs primes of 1024 bits or more,
+/// which are far too big for a `u64`, and a modulus this small can be factored in moments. For
+/// anything that needs to be secure, use a big integer crate like `num-bigint` and a vetted RSA
+/// implementation.
+pub fn generate_rsa_prime(bits: u32) -> u64 {
+    assert!((3..=RSA_MAX_BITS).contains(&bits), "RSA primes here have to be from 3 to {} bits", RSA_MAX_BITS);
+    let mut rng = rand::thread_rng();
+    loop {
+        let candidate = rng.gen_range(1 << (bits - 1)..1 << bits) | 1;
+        if miller_rabin_random(candidate, RSA_MILLER_RABIN_ROUNDS, &mut rng) {
+            return candidate;
+        }
+    }
+}
+
+/// Two different random primes, each exactly `bits` wide, for a toy RSA modulus. See
+/// [generate_rsa_prime] for how they're made and why they aren't fit for real keys.
+pub fn generate_rsa_primes(bits: u32) -> (u64, u64) {
+    let p = generate_rsa_prime(bits);
+    loop {
+        let q = generate_rsa_prime(bits);
+        if q != p {
+            return (p, q);
+        }
+    }
+}
+
+/// The same as [generate_rsa_primes], but on a blocking thread, so this has to be `spawn`ed onto
+/// the runtime.
+pub async fn generate_rsa_primes_blocking(bits: u32) -> (u64, u64) {
+    poll_fn(|_| blocking(|| generate_rsa_primes(bits))).await.expect("Couldn't block")
+}
+
+/// The smallest `x` with `base^x ≡ result (mod modulus)`, or `None` if there isn't one. Panics if
+/// `modulus` is 0.
+///
+/// This is the discrete logarithm, the inverse of modular exponentiation, and it's what makes
+/// Diffie-Hellman work: `base^x` is quick to compute, but getting `x` back is slow. The
+/// baby-step giant-step algorithm gets it in `O(√modulus)` time by writing `x` as `i * s - j`,
+/// with `s` about `√modulus`. It stores `result * base^j` for every `j` below `s` in a
+/// `HashMap` (the baby steps), then looks up `base^(i * s)` for each `i` from 1 on (the giant
+/// steps) until one matches. That makes it `O(√modulus)` memory too: fine for a modulus up to
+/// about 10^12, but a modulus near `u64::MAX` would need a table of 4 billion entries.
+///
+/// The giant steps only work if `base` has an inverse mod `modulus`. If it doesn't, we first
+/// divide out the factors `base` and `modulus` have in common, one step of `x` at a time.
+pub fn discrete_log(base: u64, result: u64, modulus: u64) -> Option<u64> {
+    assert!(modulus > 0, "There's no arithmetic mod 0");
+    let (base, mut result, mut modulus) = (base % modulus, result % modulus, modulus);
+    if result == 1 % modulus {
+        return Some(0);
+    }
+    // Solve factor * base^(x - steps) ≡ result, taking out the common factors one at a time
+    let (mut factor, mut steps) = (1 % modulus, 0);
+    loop {
+        let g = gcd(base, modulus);
+        if g == 1 {
+            break;
+        }
+        if !result.is_multiple_of(g) {
+            return None;
+        }
+        result /= g;
+        modulus /= g;
+        steps += 1;
+        factor = modular_multiplication(factor, base / g, modulus);
+        if factor == result {
+            return Some(steps);
+        }
+    }
+    let s = modulus.isqrt() + 1;
+    let mut baby_steps = HashMap::new();
+    let mut step = result;
+    for j in 0..s {
+        // A later j gives a smaller x, so it wins if two baby steps are the same
+        baby_steps.insert(step, j);
+        step = modular_multiplication(step, base, modulus);
+    }
+    let giant = modular_exponentiation(base, s, modulus);
+    let mut step = factor;
+    for i in 1..=s {
+        step = modular_multiplication(step, giant, modulus);
+        if let Some(&j) = baby_steps.get(&step) {
+            return Some(i * s - j + steps);
+        }
+    }
+    None
+}
+
+/// The same as [discrete_log], but on a blocking thread, so this has to be `spawn`ed onto the
+/// runtime.
+pub async fn discrete_log_blocking(base: u64, result: u64, modulus: u64) -> Option<u64> {
+    poll_fn(|_| blocking(|| discrete_log(base, result, modulus))).await.expect("Couldn't block")
+}
+
+/// The prime zeta function `P(s)`, the sum of `p^-s` over every prime `p`, approximated by its
+/// first `terms` terms.
+///
+/// It's related to the Riemann zeta function by `ln ζ(s) = Σ P(ks) / k`, and like `ζ(s)` it only
+/// converges for `s > 1`: below that, the sum just keeps growing with `terms`. It converges fast
+/// for larger `s` and slowly near 1. After `terms` primes, the rest of the sum is about
+/// `1 / ((s - 1) p^(s-1) ln p)`, with `p` the last prime, so 10,000 terms of `P(2)` ≈ 0.452247 are
+/// out by less than 10^-6.
+pub fn compute_prime_zeta(s: f64, terms: u64) -> f64 {
+    PrimeIterator::starting_from(2).take(terms as usize).map(|p| (p as f64).powf(-s)).sum()
+}
+
+/// The same as [compute_prime_zeta], but on a blocking thread, so this has to be `spawn`ed onto the
+/// runtime.
+pub async fn compute_prime_zeta_blocking(s: f64, terms: u64) -> f64 {
+    poll_fn(|_| blocking(|| compute_prime_zeta(s, terms))).await.expect("Couldn't block")
+}
+
+/// The lengths, from 2 to `max_length`, at which the base `base` repunit is prime. Panics if
+/// `base` is less than 2.
+///
+/// A repunit is a number written with nothing but 1s: `(base^length - 1) / (base - 1)`, like 111
+/// in base 10, or 11111 in binary. Base 2 repunits are the Mersenne numbers `2^length - 1`, so
+/// `find_prime_repunits(2, 31)` is `[2, 3, 5, 7, 13, 17, 19, 31]`. In base 10, the first three are
+/// at lengths 2, 19 and 23 (the next is 317).
+///
+/// A repunit is only prime if its length is, so only prime lengths get tested, with
+/// [is_prime_u128]. Repunits grow quickly, though, and lengths whose repunit doesn't fit in a
+/// `u128` aren't checked: that's anything past 128 in binary, and past 39 in base 10.
+pub fn find_prime_repunits(base: u64, max_length: usize) -> Vec<usize> {
+    assert!(base >= 2, "Repunits need a base of at least 2");
+    let mut lengths = Vec::new();
+    let mut repunit = 1u128;
+    for length in 2..=max_length {
+        repunit = match repunit.checked_mul(base as u128).and_then(|r| r.checked_add(1)) {
+            Some(repunit) => repunit,
+            None => break,
+        };
+        if is_prime_fast(length as u64) && is_prime_u128(repunit) {
+            lengths.push(length);
+        }
+    }
+    lengths
+}
+
+/// The same as [find_prime_repunits], but on a blocking thread, so this has to be `spawn`ed onto
+/// the runtime.
+pub async fn find_prime_repunits_blocking(base: u64, max_length: usize) -> Vec<usize> {
+    poll_fn(|_| blocking(|| find_prime_repunits(base, max_length))).await.expect("Couldn't block")
+}
+
+/// `li(2)`, where [log_integral] and [prime_number_race_pi_vs_li] start integrating from.
+const LI_2: f64 = 1.045_163_780_117_493;
+
+/// Simpson's rule for `∫ dt / ln t` from `a` to `b`. It's accurate for wide ranges as well as
+/// narrow ones, since `1 / ln t` barely curves once `t` is past 2.
+fn simpson_inverse_log(a: f64, b: f64) -> f64 {
+    let f = |t: f64| 1.0 / t.ln();
+    (b - a) / 6.0 * (f(a) + 4.0 * f((a + b) / 2.0) + f(b))
+}
+
+/// The logarithmic integral `li(x) = ∫ dt / ln t` from 0 to `x`, for `x` of at least 2. Gauss
+/// noticed that `li(x)` is a remarkably good guess at `π(x)`: at `x = 10^7` it's out by 339.
+///
+/// We start from the known value of `li(2)` and integrate the rest numerically. Substituting
+/// `t = e^u` turns the integrand into the much smoother `e^u / u`, so 10,000 steps of
+/// Simpson's rule are enough for about 12 significant figures right across the `u64` range.
+pub fn log_integral(x: f64) -> f64 {
+    assert!(x >= 2.0, "log_integral only works from 2 up");
+    const STEPS: usize = 10_000;
+    let (low, high) = (2f64.ln(), x.ln());
+    let h = (high - low) / STEPS as f64;
+    let f = |u: f64| u.exp() / u;
+    let odd = (0..STEPS / 2).map(|i| f(low + (2 * i + 1) as f64 * h)).sum::<f64>();
+    let even = (1..STEPS / 2).map(|i| f(low + (2 * i) as f64 * h)).sum::<f64>();
+    LI_2 + h / 3.0 * (f(low) + 4.0 * odd + 2.0 * even + f(high))
+}
+
+/// The primes up to `limit` at which `π(x) - li(x)` changes sign, each with its new sign (`1` once
+/// `π(x)` is ahead, or `-1` once `li(x)` is).
+///
+/// `li(x)` starts ahead of `π(x)`, and it stays ahead as far as anyone has ever counted primes.
+/// Littlewood proved in 1914 that `π(x)` overtakes it eventually, and in fact infinitely often,
+/// but the first time is somewhere past 10^19 and, as far as anyone has proved, below about
+/// `1.4 * 10^316`. Skewes' original bound for it was far larger still. So below that, this
+/// always comes back empty.
+///
+/// `π(x)` comes from [sieve_primes], so `limit` is only as big as the sieve's memory allows.
+/// `li(x)` is integrated from prime to prime with Simpson's rule, starting from `li(2)`. The
+/// sign can only change at a prime, since `π(x)` only goes up there and `li(x)` keeps climbing
+/// in between.
+pub fn prime_number_race_pi_vs_li(limit: u64) -> Vec<(u64, i64)> {
+    race_crossovers(&sieve_primes(limit))
+}
+
+/// [prime_number_race_pi_vs_li], given every prime up to the limit, in order.
+fn race_crossovers(primes: &[u64]) -> Vec<(u64, i64)> {
+    let mut crossovers = Vec::new();
+    let (mut li, mut previous, mut sign) = (LI_2, 2.0, -1);
+    for (i, &p) in primes.iter().enumerate() {
+        li += simpson_inverse_log(previous, p as f64);
+        previous = p as f64;
+        let pi = (i + 1) as f64;
+        let new_sign = (pi - li).signum() as i64;
+        if new_sign != sign {
+            crossovers.push((p, new_sign));
+            sign = new_sign;
+        }
+    }
+    crossovers
+}
+
+/// Print `π(x)` next to `li(x)` for each power of ten up to `limit`, and any crossovers found by
+/// [prime_number_race_pi_vs_li].
+pub fn print_prime_number_race(limit: u64) {
+    println!("{:>20}  {:>18}  {:>22}  {:>12}", "x", "pi(x)", "li(x)", "pi(x) - li(x)");
+    let primes = sieve_primes(limit);
+    for x in std::iter::successors(Some(10u64), |x| x.checked_mul(10)).take_while(|&x| x <= limit) {
+        let pi = primes.partition_point(|&p| p <= x);
+        let li = log_integral(x as f64);
+        println!("{:>20}  {:>18}  {:>22.1}  {:>12.1}", x, pi, li, pi as f64 - li);
+    }
+    let crossovers = race_crossovers(&primes);
+    if crossovers.is_empty() {
+        println!("li(x) stays ahead of pi(x) all the way to {}", limit);
+    }
+    for (p, sign) in crossovers {
+        println!("{} pulls ahead at {}", if sign > 0 { "pi(x)" } else { "li(x)" }, p);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_gap_over_3_is_7_to_11() {
+        assert_eq!(prime_gaps_above(3, 1), vec![(7, 11)]);
+    }
+
+    #[test]
+    fn each_maximal_gap_is_the_first_above_the_last() {
+        // Stop below 155,921, past which the search gets slow in a debug build
+        for pair in KNOWN_MAXIMAL_PRIME_GAPS[..14].windows(2) {
+            let (p, q) = pair[0];
+            assert_eq!(prime_gaps_above(q - p, 1), vec![pair[1]]);
+        }
+    }
+
+    #[test]
+    fn primes_one_more_than_a_multiple_of_6() {
+        assert_eq!(find_prime_arithmetic_progressions(1, 6, 5), Ok(vec![7, 13, 19, 31, 37]));
+        let blocking = crate::prime_test! { crate::spawn_with_handle(prime_ap_blocking(5, 6, 4)).await };
+        assert_eq!(blocking, Ok(vec![5, 11, 17, 23]));
+    }
+
+    #[test]
+    fn progressions_with_a_common_factor_are_rejected() {
+        assert_eq!(find_prime_arithmetic_progressions(4, 6, 5), Err(InvalidProgressionError { start: 4, diff: 6 }));
+        assert_eq!(find_prime_arithmetic_progressions(7, 0, 1), Err(InvalidProgressionError { start: 7, diff: 0 }));
+        let error = InvalidProgressionError { start: 4, diff: 6 };
+        assert_eq!(error.to_string(), "4 + k * 6 has at most one prime, since gcd(4, 6) = 2");
+    }
+
+    #[test]
+    fn factors_of_60_stream_in_order() {
+        crate::prime_test! {
+            assert_eq!(stream_prime_factors(60).await.collect::<Vec<_>>().await, vec![(2, 2), (3, 1), (5, 1)]);
+            assert!(stream_prime_factors(1).await.collect::<Vec<_>>().await.is_empty());
+        }
+    }
+
+    #[test]
+    fn big_factors_come_from_pollards_rho() {
+        let (p, q) = (2_147_483_647, 2_147_483_629);
+        let mut factors = PrimeFactors::new(4 * p * q).collect::<Vec<_>>();
+        assert_eq!(factors.remove(0), (2, 2));
+        factors.sort_unstable();
+        assert_eq!(factors, vec![(q, 1), (p, 1)]);
+        for n in (2..100_000).step_by(991) {
+            let expanded = PrimeFactors::new(n).flat_map(|(p, e)| std::iter::repeat_n(p, e as usize));
+            assert_eq!(expanded.collect::<Vec<_>>(), prime_factorization(n, None), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn rsa_primes_are_prime_and_the_right_width() {
+        use crate::primality::baillie_psw;
+        for bits in 3..=RSA_MAX_BITS {
+            let (p, q) = generate_rsa_primes(bits);
+            assert_ne!(p, q);
+            for prime in [p, q] {
+                assert!(baillie_psw(prime), "{} isn't prime", prime);
+                assert_eq!(u64::BITS - prime.leading_zeros(), bits, "{} isn't {} bits wide", prime, bits);
+            }
+        }
+        let (p, q) = crate::prime_test! { crate::spawn_with_handle(generate_rsa_primes_blocking(16)).await };
+        assert!(p != q && baillie_psw(p) && baillie_psw(q));
+    }
+
+    #[test]
+    #[should_panic(expected = "from 3 to 32 bits")]
+    fn rsa_primes_are_at_most_32_bits() {
+        generate_rsa_prime(RSA_MAX_BITS + 1);
+    }
+
+    #[test]
+    fn discrete_logs_mod_11() {
+        assert_eq!(discrete_log(2, 8, 11), Some(3));
+        assert_eq!(discrete_log(2, 0, 11), None);
+        assert_eq!(discrete_log(2, 1, 11), Some(0));
+        let blocking = crate::prime_test! { crate::spawn_with_handle(discrete_log_blocking(5, 7, 1_000_003)).await };
+        let x = blocking.unwrap();
+        assert_eq!(modular_exponentiation(5, x, 1_000_003), 7);
+    }
+
+    #[test]
+    fn discrete_logs_are_the_smallest_exponent() {
+        for modulus in 1..40 {
+            for base in 0..modulus {
+                // Powers repeat with a period of less than `modulus`, after less than `modulus`
+                let powers: Vec<_> = (0..2 * modulus).map(|x| modular_exponentiation(base, x, modulus)).collect();
+                for result in 0..modulus {
+                    let expected = powers.iter().position(|&p| p == result).map(|x| x as u64);
+                    let found = discrete_log(base, result, modulus);
+                    assert_eq!(found, expected, "{}^x = {} mod {}", base, result, modulus);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn prime_zeta_of_2() {
+        // P(2) = 0.4522474200..., and the first 10,000 terms leave out less than 10^-6 of it
+        assert!((compute_prime_zeta(2.0, 10_000) - 0.452_247_42).abs() < 1e-5);
+        assert!((compute_prime_zeta(2.0, 3) - (1.0 / 4.0 + 1.0 / 9.0 + 1.0 / 25.0)).abs() < 1e-15);
+        let blocking = crate::prime_test! { crate::spawn_with_handle(compute_prime_zeta_blocking(2.0, 10_000)).await };
+        assert_eq!(blocking, compute_prime_zeta(2.0, 10_000));
+    }
+
+    #[test]
+    fn bezout_coefficients() {
+        assert_eq!(extended_gcd(35, 15), (1, -2, 5));
+        assert_eq!(extended_gcd(7, 0), (1, 0, 7));
+        assert_eq!(extended_gcd(0, 7), (0, 1, 7));
+        assert_eq!(extended_gcd(7, 7), (0, 1, 7));
+        let (x, y, g) = extended_gcd(u64::MAX, u64::MAX - 1);
+        assert_eq!(g, 1);
+        assert_eq!(x as i128 * u64::MAX as i128 + y as i128 * (u64::MAX - 1) as i128, 1);
+    }
+
+    #[test]
+    fn inverses_and_the_chinese_remainder_theorem() {
+        assert_eq!(modular_inverse(3, 11), Some(4));
+        assert_eq!(modular_inverse(4, 6), None);
+        assert_eq!(modular_inverse(5, 0), None);
+        assert_eq!(chinese_remainder_theorem(&[(2, 3), (3, 5), (2, 7)]), Some(23));
+        assert_eq!(chinese_remainder_theorem(&[(1, 4), (2, 6)]), None);
+    }
+
+    #[test]
+    fn binary_repunits_are_the_mersenne_primes() {
+        let mersenne_exponents = vec![2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127];
+        assert_eq!(find_prime_repunits(2, 200), mersenne_exponents);
+        let blocking = crate::prime_test! { crate::spawn_with_handle(find_prime_repunits_blocking(2, 31)).await };
+        assert_eq!(blocking, vec![2, 3, 5, 7, 13, 17, 19, 31]);
+    }
+
+    #[test]
+    fn base_10_repunit_primes() {
+        assert_eq!(find_prime_repunits(10, 40), vec![2, 19, 23]);
+        assert_eq!(find_prime_repunits(10, 1), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn li_stays_ahead_of_pi() {
+        assert_eq!(prime_number_race_pi_vs_li(10_000_000), vec![]);
+        assert_eq!(prime_number_race_pi_vs_li(1), vec![]);
+    }
+
+    #[test]
+    fn li_stays_ahead_of_pi_to_10_to_the_8() {
+        // Sieving all the way to 10^8 is slow in a debug build, so past 10^7 we count the primes at
+        // checkpoints instead
+        for x in (10_000_000..=100_000_000).step_by(500_000) {
+            let pi = crate::analytic::legendre_pi(x);
+            assert!((pi as f64) < log_integral(x as f64), "pi({}) = {} is ahead", x, pi);
+        }
+        assert_eq!(crate::analytic::legendre_pi(100_000_000), 5_761_455);
+    }
+}
@@ -0,0 +1,180 @@
+//! Formatting search results for people to read.
+
+use crate::PrimeResult;
+use std::time::Duration;
+
+/// A duration in the most readable units for its size: `< 1ms`, `42ms`, `3.14s`, `5m 12s` or
+/// `2h 3m`. Anything below the units shown is truncated.
+pub fn format_duration_human(d: Duration) -> String {
+    let secs = d.as_secs();
+    if d < Duration::from_millis(1) {
+        "< 1ms".to_string()
+    } else if secs < 1 {
+        format!("{}ms", d.as_millis())
+    } else if secs < 60 {
+        format!("{}.{:02}s", secs, d.subsec_millis() / 10)
+    } else if secs < 3600 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+/// A duration in seconds, to the millisecond: `312.824s`.
+pub fn format_duration_compact(d: Duration) -> String {
+    format!("{:.3}s", d.as_secs_f64())
+}
+
+/// Where a value sits in its column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableAlign {
+    Left,
+    Right,
+    Center,
+}
+
+/// Which field of a [PrimeResult] a column shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnKind {
+    Id,
+    N,
+    Value,
+    ElapsedSecs,
+}
+
+/// A column of a results table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub kind: ColumnKind,
+    /// The header for the column
+    pub name: String,
+    /// The width of the column. Columns grow to fit their header if this is too narrow.
+    pub width: usize,
+    pub align: TableAlign,
+}
+
+impl Column {
+    pub fn new(kind: ColumnKind, name: &str, width: usize, align: TableAlign) -> Self {
+        Column { kind, name: name.to_string(), width, align }
+    }
+
+    pub fn id() -> Self {
+        Column::new(ColumnKind::Id, "#", 2, TableAlign::Right)
+    }
+
+    pub fn n() -> Self {
+        Column::new(ColumnKind::N, "n", 6, TableAlign::Right)
+    }
+
+    pub fn value() -> Self {
+        Column::new(ColumnKind::Value, "prime", 12, TableAlign::Right)
+    }
+
+    pub fn elapsed_secs() -> Self {
+        Column::new(ColumnKind::ElapsedSecs, "time (s)", 6, TableAlign::Right)
+    }
+
+    fn width(&self) -> usize {
+        self.width.max(self.name.chars().count())
+    }
+
+    fn cell(&self, r: &PrimeResult) -> String {
+        match self.kind {
+            ColumnKind::Id => r.id.to_string(),
+            ColumnKind::N => r.n.to_string(),
+            ColumnKind::Value => r.value.to_string(),
+            ColumnKind::ElapsedSecs => format!("{:.3}", r.elapsed.as_secs_f64()),
+        }
+    }
+
+    fn pad(&self, text: &str) -> String {
+        let w = self.width();
+        match self.align {
+            TableAlign::Left => format!("{:<w$}", text, w = w),
+            TableAlign::Right => format!("{:>w$}", text, w = w),
+            TableAlign::Center => format!("{:^w$}", text, w = w),
+        }
+    }
+}
+
+/// Lays results out as a table, with a header row and a separator row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableFormatter {
+    pub columns: Vec<Column>,
+}
+
+impl Default for TableFormatter {
+    /// The same columns as `PrimeResult`'s `Display` output, though the time is always in seconds.
+    fn default() -> Self {
+        TableFormatter { columns: vec![Column::id(), Column::n(), Column::value(), Column::elapsed_secs()] }
+    }
+}
+
+impl TableFormatter {
+    /// Set the alignment of every column to `align`.
+    pub fn aligned(mut self, align: TableAlign) -> Self {
+        for column in &mut self.columns {
+            column.align = align;
+        }
+        self
+    }
+
+    /// Format `results` as a table, one row per result. Every row, including the last, ends in a
+    /// newline.
+    pub fn format(&self, results: &[PrimeResult]) -> String {
+        let row = |cells: Vec<String>| cells.join("  ").trim_end().to_string() + "\n";
+        let mut table = row(self.columns.iter().map(|c| c.pad(&c.name)).collect());
+        table.push_str(&row(self.columns.iter().map(|c| "-".repeat(c.width())).collect()));
+        for r in results {
+            table.push_str(&row(self.columns.iter().map(|c| c.pad(&c.cell(r))).collect()));
+        }
+        table
+    }
+}
+
+/// Format `results` as a table with the default columns, all aligned with `align`.
+pub fn format_prime_table(results: &[PrimeResult], align: TableAlign) -> String {
+    TableFormatter::default().aligned(align).format(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn result(id: u64, n: u64, value: u64, millis: u64) -> PrimeResult {
+        let elapsed = Duration::from_millis(millis);
+        PrimeResult { id, n, value, started_at: Instant::now(), elapsed, peak_memory: 0 }
+    }
+
+    #[test]
+    fn right_aligned_table() {
+        let results = [result(0, 10, 29, 1), result(1, 1000, 7919, 250), result(12, 100_000, 1_299_709, 12_345)];
+        let expected = concat!(
+            " #       n         prime  time (s)\n",
+            "--  ------  ------------  --------\n",
+            " 0      10            29     0.001\n",
+            " 1    1000          7919     0.250\n",
+            "12  100000       1299709    12.345\n",
+        );
+        assert_eq!(format_prime_table(&results, TableAlign::Right), expected);
+    }
+
+    #[test]
+    fn human_durations() {
+        assert_eq!(format_duration_human(Duration::from_micros(999)), "< 1ms");
+        assert_eq!(format_duration_human(Duration::from_millis(42)), "42ms");
+        assert_eq!(format_duration_human(Duration::from_millis(3140)), "3.14s");
+        assert_eq!(format_duration_human(Duration::from_millis(1005)), "1.00s");
+        // Rounding would make this 60.00s
+        assert_eq!(format_duration_human(Duration::from_millis(59_999)), "59.99s");
+        assert_eq!(format_duration_human(Duration::from_secs(60)), "1m 0s");
+        assert_eq!(format_duration_human(Duration::from_secs(312)), "5m 12s");
+        assert_eq!(format_duration_human(Duration::from_secs(2 * 3600 + 3 * 60 + 59)), "2h 3m");
+    }
+
+    #[test]
+    fn compact_durations() {
+        assert_eq!(format_duration_compact(Duration::from_millis(312_824)), "312.824s");
+    }
+}
@@ -0,0 +1,171 @@
+//! Finding the `n`th prime with several threads testing candidates at once.
+//!
+//! [crate::find_nth_prime] tests one candidate after another. Here, `parallelism` searches on
+//! blocking threads share a counter, and each one takes the next untested candidate whenever it's
+//! ready for more. The primes they find come back on a channel, in whatever order the searches
+//! happen to finish them.
+//!
+//! The tricky part is knowing when we can start counting. If thread A confirms 101 while thread B
+//! is still testing 97, then we can't say 101 is the 26th prime until B is done. So every search
+//! publishes the candidate it's working on, and we only count a prime once every search has moved
+//! past it. Until then, primes wait in a min-heap so we can count them off in order.
+//!
+//! [find_nth_prime_parallel_sieve] takes the other approach: work out how far the `n`th prime can
+//! possibly be, and sieve that whole range in segments, one blocking thread per segment.
+
+use crate::estimation::nth_prime_upper_bound;
+use crate::sieve::segmented_sieve;
+use crate::{is_prime, spawn_with_handle};
+use futures::channel::mpsc;
+use futures::future::{join_all, poll_fn};
+use futures::stream::StreamExt;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tokio_executor::threadpool::blocking;
+
+/// A search that isn't working on a candidate
+const IDLE: u64 = u64::MAX;
+
+struct Shared {
+    /// The smallest candidate nobody has claimed yet
+    next: AtomicU64,
+    /// No search is working on anything smaller than its entry here
+    working_on: Vec<AtomicU64>,
+    stop: AtomicBool,
+}
+
+impl Shared {
+    /// Every candidate below this has been tested, and any primes among them have been sent.
+    fn finished_below(&self) -> u64 {
+        // `next` has to be read first. A search publishes where it's up to before it claims a
+        // candidate, so any candidate claimed before this read shows up in `working_on` below.
+        let next = self.next.load(Ordering::SeqCst);
+        self.working_on.iter().map(|w| w.load(Ordering::SeqCst)).fold(next, u64::min)
+    }
+}
+
+fn search(shared: &Shared, worker: usize, primes: &mpsc::UnboundedSender<u64>) {
+    let working_on = &shared.working_on[worker];
+    while !shared.stop.load(Ordering::SeqCst) {
+        working_on.store(shared.next.load(Ordering::SeqCst), Ordering::SeqCst);
+        let candidate = shared.next.fetch_add(1, Ordering::SeqCst);
+        working_on.store(candidate, Ordering::SeqCst);
+        if is_prime(candidate) && primes.unbounded_send(candidate).is_err() {
+            break;
+        }
+    }
+    working_on.store(IDLE, Ordering::SeqCst);
+}
+
+/// Find the `n`th prime (counting from 1), with `parallelism` searches on blocking threads testing
+/// candidates at the same time. The future spawns the searches, so it has to run inside the
+/// runtime. Panics if `n` is 0.
+///
+/// Searches that can't get a blocking thread straight away just join in late, but there's no
+/// point asking for more than the runtime has, or than there are cores.
+pub fn find_nth_prime_concurrent_candidates(n: u64, parallelism: usize) -> impl Future<Output = u64> {
+    assert!(n > 0, "There is no 0th prime");
+    let parallelism = parallelism.max(1);
+    async move {
+        let shared = Arc::new(Shared {
+            next: AtomicU64::new(2),
+            working_on: (0..parallelism).map(|_| AtomicU64::new(IDLE)).collect(),
+            stop: AtomicBool::new(false),
+        });
+        let (tx, mut rx) = mpsc::unbounded();
+        for worker in 0..parallelism {
+            let (shared, tx) = (shared.clone(), tx.clone());
+            tokio::spawn(async move {
+                poll_fn(|_| blocking(|| search(&shared, worker, &tx))).await.expect("Couldn't block")
+            });
+        }
+        drop(tx);
+        let mut pending = BinaryHeap::new();
+        let mut found = 0;
+        while let Some(p) = rx.next().await {
+            pending.push(Reverse(p));
+            let finished_below = shared.finished_below();
+            while let Ok(Some(p)) = rx.try_next() {
+                pending.push(Reverse(p));
+            }
+            while let Some(&Reverse(p)) = pending.peek() {
+                if p >= finished_below {
+                    break;
+                }
+                pending.pop();
+                found += 1;
+                if found == n {
+                    shared.stop.store(true, Ordering::SeqCst);
+                    return p;
+                }
+            }
+        }
+        unreachable!("The searches stopped before finding the {}th prime", n)
+    }
+}
+
+/// How many segments [find_nth_prime_parallel_sieve] splits its range into.
+pub const SIEVE_SEGMENTS: u64 = 4;
+
+/// The ranges to sieve to be sure of finding the `n`th prime, split into [SIEVE_SEGMENTS] pieces.
+///
+/// [nth_prime_upper_bound] is proven never to underestimate the `n`th prime, so that's how far we
+/// sieve, and one pass is always enough.
+fn nth_prime_sieve_segments(n: u64) -> Vec<(u64, u64)> {
+    let limit = nth_prime_upper_bound(n) + 1;
+    let segment_size = limit.div_ceil(SIEVE_SEGMENTS);
+    (0..SIEVE_SEGMENTS).map(|i| (i * segment_size, ((i + 1) * segment_size).min(limit))).collect()
+}
+
+fn nth_from_segments(n: u64, segments: &[Vec<u64>]) -> u64 {
+    segments.iter().flatten().nth((n - 1) as usize).copied().expect("The sieve didn't reach the nth prime")
+}
+
+/// Find the `n`th prime (counting from 1) by sieving up to the most it could possibly be, with
+/// each of [SIEVE_SEGMENTS] segments sieved on its own blocking thread. Panics if `n` is 0.
+///
+/// The upper bound overshoots by about 0.01% at `n = 1,000,000`, but the sieve needs a byte for
+/// every number in the range, so the 10 millionth prime still takes about 180MB.
+pub async fn find_nth_prime_parallel_sieve(n: u64) -> u64 {
+    assert!(n > 0, "There is no 0th prime");
+    let segments = nth_prime_sieve_segments(n).into_iter().map(|(low, high)| {
+        spawn_with_handle(async move {
+            poll_fn(|_| blocking(|| segmented_sieve(low, high))).await.expect("Couldn't block")
+        })
+    });
+    let segments = join_all(segments.collect::<Vec<_>>()).await;
+    nth_from_segments(n, &segments)
+}
+
+/// The same sieve as [find_nth_prime_parallel_sieve], for code that isn't async: each segment
+/// gets a plain OS thread instead of a blocking thread.
+pub fn find_nth_prime_threaded_sieve(n: u64) -> u64 {
+    assert!(n > 0, "There is no 0th prime");
+    let segments = thread::scope(|scope| {
+        let handles = nth_prime_sieve_segments(n)
+            .into_iter()
+            .map(|(low, high)| scope.spawn(move || segmented_sieve(low, high)))
+            .collect::<Vec<_>>();
+        handles.into_iter().map(|h| h.join().expect("A sieve thread panicked")).collect::<Vec<_>>()
+    });
+    nth_from_segments(n, &segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::find_nth_prime;
+
+    #[test]
+    fn concurrent_candidates_agree_with_find_nth_prime() {
+        crate::prime_test! {
+            for &n in &[1, 100, 1000] {
+                assert_eq!(find_nth_prime_concurrent_candidates(n, 4).await, find_nth_prime(n), "n = {}", n);
+            }
+        }
+    }
+}
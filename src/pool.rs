@@ -0,0 +1,157 @@
+//! Keeping a lid on how much work is in flight at once.
+//!
+//! Spawning every search up front is fine for 20 searches, but not for 20,000: each one queues up
+//! for a blocking thread and holds on to its memory while it waits. A [ComputePool] lets a
+//! producer submit work in a loop, making it wait whenever `max_in_flight` tasks are already
+//! running, and collect the results in batches as it goes.
+
+use crate::{prime_output, spawn_with_handle, PrimeResult};
+use futures::future::{poll_fn, RemoteHandle};
+use std::future::Future;
+use std::sync::Arc;
+use tokio_sync::semaphore::{Permit, Semaphore};
+
+/// Gives a permit back to the semaphore when the task holding it finishes, even if it panics.
+struct PermitGuard {
+    permit: Permit,
+    semaphore: Arc<Semaphore>,
+}
+
+impl Drop for PermitGuard {
+    fn drop(&mut self) {
+        self.permit.release(&self.semaphore);
+    }
+}
+
+/// Runs no more than `max_in_flight` submitted futures at a time, and hands their outputs back in
+/// the order they were submitted.
+pub struct ComputePool<T: Send + 'static> {
+    /// Every task submitted since the last drain, in the order they were submitted
+    handles: Vec<RemoteHandle<T>>,
+    max_in_flight: usize,
+    semaphore: Arc<Semaphore>,
+}
+
+impl<T: Send + 'static> ComputePool<T> {
+    pub fn new(max_in_flight: usize) -> Self {
+        let max_in_flight = max_in_flight.max(1);
+        ComputePool { handles: Vec::new(), max_in_flight, semaphore: Arc::new(Semaphore::new(max_in_flight)) }
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+
+    /// Spawn `fut` as soon as there's room for it. This waits while `max_in_flight` tasks are
+    /// already running, which is the back pressure that keeps a producer from racing ahead.
+    pub async fn submit(&mut self, fut: impl Future<Output = T> + Send + 'static) {
+        let mut permit = Permit::new();
+        let semaphore = self.semaphore.clone();
+        poll_fn(|cx| permit.poll_acquire(cx, &semaphore)).await.expect("The pool's semaphore was closed");
+        let guard = PermitGuard { permit, semaphore };
+        self.handles.push(spawn_with_handle(async move {
+            let output = fut.await;
+            drop(guard);
+            output
+        }));
+    }
+
+    /// [submit](ComputePool::submit) each of `futs` in turn.
+    pub async fn submit_batch<F, I>(&mut self, futs: I)
+    where
+        F: Future<Output = T> + Send + 'static,
+        I: IntoIterator<Item = F>,
+    {
+        for fut in futs {
+            self.submit(fut).await;
+        }
+    }
+
+    /// Wait for everything submitted since the last drain, and return the outputs in the order the
+    /// futures were submitted.
+    pub async fn drain_results(&mut self) -> Vec<T> {
+        let mut results = Vec::with_capacity(self.handles.len());
+        for handle in self.handles.drain(..) {
+            results.push(handle.await);
+        }
+        results
+    }
+
+    /// How many tasks have been submitted since the last drain. Some of them may have finished
+    /// already.
+    pub fn pending_count(&self) -> usize {
+        self.handles.len()
+    }
+}
+
+impl ComputePool<PrimeResult> {
+    /// A pool for prime searches.
+    pub fn prime_pool(max_in_flight: usize) -> Self {
+        ComputePool::new(max_in_flight)
+    }
+
+    /// Submit a search for the `n`th prime.
+    pub async fn submit_prime(&mut self, id: u64, n: u64) {
+        self.submit(prime_output(id, n)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn drains_in_submission_order() {
+        crate::prime_test! {
+            let mut pool = ComputePool::new(4);
+            // The first task is the slowest, so the others all finish before it does
+            for (id, wait) in [40, 30, 20, 10].iter().enumerate() {
+                pool.submit(async move {
+                    tokio::timer::delay(tokio::clock::now() + Duration::from_millis(*wait)).await;
+                    id
+                })
+                .await;
+            }
+            assert_eq!(pool.pending_count(), 4);
+            assert_eq!(pool.drain_results().await, vec![0, 1, 2, 3]);
+            assert_eq!(pool.pending_count(), 0);
+            assert!(pool.drain_results().await.is_empty());
+        }
+    }
+
+    #[test]
+    fn never_runs_more_than_max_in_flight() {
+        crate::prime_test! {
+            let (running, most) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+            let mut pool = ComputePool::new(2);
+            let tasks = (0..10).map(|i| {
+                let (running, most) = (running.clone(), most.clone());
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    tokio::timer::delay(tokio::clock::now() + Duration::from_millis(5)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    i
+                }
+            });
+            pool.submit_batch(tasks).await;
+            assert_eq!(pool.drain_results().await, (0..10).collect::<Vec<_>>());
+            assert_eq!(most.load(Ordering::SeqCst), 2);
+        }
+    }
+
+    #[test]
+    fn prime_pool_runs_prime_searches() {
+        crate::prime_test! {
+            let mut pool = ComputePool::prime_pool(2);
+            for (id, n) in [(0, 10_000), (1, 10), (2, 100)].iter() {
+                pool.submit_prime(*id, *n).await;
+            }
+            let results = pool.drain_results().await;
+            let values: Vec<_> = results.iter().map(|r| (r.id, r.value)).collect();
+            assert_eq!(values, vec![(0, 104_729), (1, 29), (2, 541)]);
+        }
+    }
+}
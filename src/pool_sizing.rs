@@ -0,0 +1,110 @@
+//! Working out how many blocking threads a runtime should have.
+//!
+//! The runtime's blocking pool is a fixed size: you pick `blocking_threads` when you build it, and
+//! there's no way to grow or shrink it while it runs. So [DynamicThreadPoolSizer] only makes
+//! recommendations. Watch how the searches are getting on, feed that into
+//! [DynamicThreadPoolSizer::adjust], and build the next runtime with
+//! [DynamicThreadPoolSizer::build_runtime_for].
+//!
+//! Later versions of Tokio solve this properly: `tokio::task::spawn_blocking` hands work to a pool
+//! that starts threads as they're needed (up to a limit) and lets idle ones exit. If you can
+//! upgrade, use that instead.
+
+use tokio::runtime::{Builder, Runtime};
+
+/// Recommends a blocking thread count between `min_threads` and `max_threads`, starting at
+/// `current`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicThreadPoolSizer {
+    pub min_threads: usize,
+    pub max_threads: usize,
+    /// The current recommendation
+    pub current: usize,
+}
+
+impl DynamicThreadPoolSizer {
+    /// Start at `min_threads`.
+    pub fn new(min_threads: usize, max_threads: usize) -> Self {
+        let min_threads = min_threads.max(1);
+        DynamicThreadPoolSizer { min_threads, max_threads: max_threads.max(min_threads), current: min_threads }
+    }
+
+    /// Update the recommendation, given how many searches are waiting for a thread, and how much
+    /// work got done since the last adjustment.
+    ///
+    /// `completed_rate` is measured in threads' worth of work: the time spent in searches that
+    /// finished, divided by how long we were watching. If the queue is more than twice as long as
+    /// there are threads, we double the threads. If less than half of the threads' capacity went to
+    /// searching, they're mostly idle, so we halve them. Either way we stay between `min_threads`
+    /// and `max_threads`.
+    pub async fn adjust(&mut self, queue_depth: u64, completed_rate: f64) {
+        let capacity = self.current as f64;
+        if queue_depth > 2 * self.current as u64 {
+            self.current = (self.current * 2).min(self.max_threads);
+        } else if completed_rate < 0.5 * capacity {
+            self.current = (self.current / 2).max(self.min_threads);
+        }
+    }
+
+    /// Build a runtime with the recommended number of blocking threads, and a single core thread
+    /// like the one `main` uses.
+    pub fn build_runtime_for(&self) -> Runtime {
+        Builder::new()
+            .blocking_threads(self.current)
+            .core_threads(1)
+            .build()
+            .expect("Couldn't build the runtime")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn limits_are_normalised() {
+        let sizer = |min_threads, max_threads, current| DynamicThreadPoolSizer { min_threads, max_threads, current };
+        assert_eq!(DynamicThreadPoolSizer::new(2, 16), sizer(2, 16, 2));
+        assert_eq!(DynamicThreadPoolSizer::new(0, 0), sizer(1, 1, 1));
+        assert_eq!(DynamicThreadPoolSizer::new(8, 4).max_threads, 8);
+    }
+
+    #[test]
+    fn grows_when_the_queue_is_long() {
+        let mut sizer = DynamicThreadPoolSizer::new(2, 16);
+        // Twice as long as there are threads isn't enough
+        block_on(sizer.adjust(4, 2.0));
+        assert_eq!(sizer.current, 2);
+        block_on(sizer.adjust(5, 2.0));
+        assert_eq!(sizer.current, 4);
+        block_on(sizer.adjust(100, 4.0));
+        assert_eq!(sizer.current, 8);
+    }
+
+    #[test]
+    fn shrinks_when_the_threads_are_idle() {
+        let mut sizer = DynamicThreadPoolSizer { min_threads: 1, max_threads: 16, current: 8 };
+        // Exactly half busy is fine
+        block_on(sizer.adjust(0, 4.0));
+        assert_eq!(sizer.current, 8);
+        block_on(sizer.adjust(0, 3.9));
+        assert_eq!(sizer.current, 4);
+        // A long queue wins over idle threads
+        block_on(sizer.adjust(9, 0.0));
+        assert_eq!(sizer.current, 8);
+    }
+
+    #[test]
+    fn stays_between_the_limits() {
+        let mut sizer = DynamicThreadPoolSizer::new(3, 10);
+        for _ in 0..5 {
+            block_on(sizer.adjust(1_000, 10.0));
+        }
+        assert_eq!(sizer.current, 10);
+        for _ in 0..5 {
+            block_on(sizer.adjust(0, 0.0));
+        }
+        assert_eq!(sizer.current, 3);
+    }
+}
@@ -0,0 +1,118 @@
+//! A blocking pool whose threads are all running before the first job turns up.
+//!
+//! Later versions of Tokio hand `spawn_blocking` closures to a pool that creates its threads
+//! lazily, so the first jobs after the runtime starts wait for a thread to be created. A
+//! [PrewarmedBlockingPool] creates all its threads up front, and they wait on a channel for jobs,
+//! the same way the search threads in the `single_thread` demo do.
+//!
+//! The `blocking` in this version of Tokio works differently: it runs the closure right there on
+//! the worker thread that called it, and hands the worker's other tasks to another thread. So the
+//! job itself starts straight away, and `benches/prewarmed_pool.rs` shows it starting a little
+//! sooner than a job sent to this pool, which has to wake one of its threads up. Where this pool
+//! helps is when there's no thread pool runtime to call `blocking` on, like the `single_thread`
+//! demo, or when the blocking work shouldn't count against the runtime's `blocking_threads`.
+
+use futures::channel::oneshot;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed set of threads that run closures handed to [PrewarmedBlockingPool::submit].
+///
+/// Dropping the pool lets each thread finish the job it's on, and waits for them all to exit.
+/// Jobs still waiting in the queue at that point are run first.
+pub struct PrewarmedBlockingPool {
+    workers: Vec<JoinHandle<()>>,
+    /// Always `Some` until the pool is dropped. Dropping it is what tells the workers to stop.
+    tx: Option<mpsc::Sender<Job>>,
+}
+
+impl PrewarmedBlockingPool {
+    /// Start `threads` worker threads (at least one), and return once they've all been created.
+    pub fn new(threads: usize) -> Self {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let rx = rx.clone();
+                thread::spawn(move || loop {
+                    let job = rx.lock().unwrap().recv();
+                    match job {
+                        // A panicking job shouldn't take its thread down with it. Its caller finds
+                        // out when the result never arrives.
+                        Ok(job) => drop(panic::catch_unwind(AssertUnwindSafe(job))),
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+        PrewarmedBlockingPool { workers, tx: Some(tx) }
+    }
+
+    /// How many threads the pool has.
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Run `f` on one of the pool's threads, and wait for its result. Panics if `f` does.
+    ///
+    /// Unlike `blocking`, this doesn't need a thread pool runtime, or any runtime at all: the
+    /// result comes back on a oneshot channel, which any executor can wait on.
+    pub async fn submit<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result_tx, result_rx) = oneshot::channel();
+        let job = Box::new(move || {
+            result_tx.send(f()).ok();
+        });
+        self.tx.as_ref().expect("The pool is shutting down").send(job).expect("The pool's threads have stopped");
+        result_rx.await.expect("A job panicked")
+    }
+}
+
+impl Drop for PrewarmedBlockingPool {
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        for worker in self.workers.drain(..) {
+            worker.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primality::is_prime_fast;
+    use futures::executor::block_on;
+    use std::collections::HashSet;
+
+    #[test]
+    fn hundred_sequential_submissions_on_four_threads() {
+        let pool = PrewarmedBlockingPool::new(4);
+        assert_eq!(pool.threads(), 4);
+        let (results, threads): (Vec<_>, HashSet<_>) = block_on(async {
+            let mut runs = Vec::new();
+            for i in 0..100u64 {
+                runs.push(pool.submit(move || (is_prime_fast(i), thread::current().id())).await);
+            }
+            runs
+        })
+        .into_iter()
+        .unzip();
+        assert_eq!(results, (0..100).map(is_prime_fast).collect::<Vec<_>>());
+        assert!(threads.len() <= 4);
+        assert!(!threads.contains(&thread::current().id()));
+    }
+
+    #[test]
+    fn a_panicking_job_leaves_its_thread_running() {
+        let pool = PrewarmedBlockingPool::new(1);
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| block_on(pool.submit(|| panic!("Boom")))));
+        assert!(panicked.is_err());
+        assert_eq!(block_on(pool.submit(|| 6 * 7)), 42);
+    }
+}
@@ -0,0 +1,646 @@
+//! Fast primality tests.
+//!
+//! [crate::is_prime] is deliberately slow, so that the demo has something to chew on. These are
+//! the tests you'd actually want to use.
+
+use crate::math_utils::{modular_exponentiation, modular_multiplication};
+use crate::number_theory::gcd;
+use crate::parallel_search::find_nth_prime_threaded_sieve;
+use crate::sieve::first_primes;
+use crate::{time_search, PrimeResult};
+use futures::future::poll_fn;
+use rand::Rng;
+use std::sync::OnceLock;
+use tokio_executor::threadpool::blocking;
+
+include!(concat!(env!("OUT_DIR"), "/small_primes_bitset.rs"));
+
+/// Testing against every one of these bases is enough to make Miller-Rabin deterministic for every
+/// `u64`.
+pub const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// Look `n` up in [SMALL_PRIMES_BITSET]. Only numbers below 65536 are in the table, so this
+/// returns `None` for anything larger.
+pub fn is_prime_lookup_table(n: u64) -> Option<bool> {
+    if n >= 65536 {
+        return None;
+    }
+    let word = SMALL_PRIMES_BITSET[(n / 64) as usize];
+    Some(word & (1 << (n % 64)) != 0)
+}
+
+/// The Miller-Rabin test of `n` against each of the `witnesses`. A `false` means `n` is definitely
+/// composite. A `true` means `n` is prime, or is a strong pseudoprime to every one of the witnesses.
+pub fn miller_rabin(n: u64, witnesses: &[u64]) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for &p in witnesses {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    // Write n - 1 as d * 2^s with d odd
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    witnesses.iter().all(|&a| {
+        let mut x = modular_exponentiation(a, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = modular_multiplication(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// A primality test that's fast for every `u64`: a table lookup for small values, and
+/// deterministic Miller-Rabin for everything else.
+pub fn is_prime_fast(n: u64) -> bool {
+    is_prime_lookup_table(n).unwrap_or_else(|| miller_rabin(n, &WITNESSES))
+}
+
+/// The primes in increasing order, tested one candidate at a time with [is_prime_fast].
+///
+/// The iterator ends after the largest prime that fits in a `u64`.
+#[derive(Debug, Clone)]
+pub struct PrimeIterator {
+    /// The next number to test, or `None` once we've run past `u64::MAX`
+    next: Option<u64>,
+}
+
+impl PrimeIterator {
+    /// Iterate over the primes that are at least `start`.
+    pub fn starting_from(start: u64) -> Self {
+        PrimeIterator { next: Some(start) }
+    }
+}
+
+impl Iterator for PrimeIterator {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        while let Some(candidate) = self.next {
+            self.next = candidate.checked_add(1);
+            if is_prime_fast(candidate) {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+}
+
+/// The `k`th prime that is at least `start`, counting from 1. So `kth_prime_ge(1, 10)` is 11, and
+/// `kth_prime_ge(3, 10)` is 17.
+///
+/// This panics if `k` is 0, or if there are fewer than `k` primes between `start` and `u64::MAX`.
+/// The gaps between primes that size are only a few hundred wide, so you'll only hit that with
+/// `start` right at the top of the range; long before then the search will take forever anyway.
+pub fn kth_prime_ge(k: u64, start: u64) -> u64 {
+    assert!(k > 0, "There is no 0th prime");
+    PrimeIterator::starting_from(start)
+        .nth((k - 1) as usize)
+        .unwrap_or_else(|| panic!("There are fewer than {} primes from {} up to u64::MAX", k, start))
+}
+
+/// The same as [kth_prime_ge], but the search runs on a blocking thread, so this has to be
+/// `spawn`ed onto the runtime.
+pub async fn kth_prime_ge_blocking(k: u64, start: u64) -> u64 {
+    poll_fn(|_| blocking(|| kth_prime_ge(k, start))).await.expect("Couldn't block")
+}
+
+/// The `k`th prime below `below`, counting down from 1. So `kth_prime_lt(1, 10)` is 7. Returns
+/// `None` if `k` is 0, or if there are fewer than `k` primes below `below`.
+pub fn kth_prime_lt(k: u64, below: u64) -> Option<u64> {
+    if k == 0 {
+        return None;
+    }
+    (0..below).rev().filter(|&n| is_prime_fast(n)).nth((k - 1) as usize)
+}
+
+/// Trial division, but skipping every candidate divisor that's a multiple of 2, 3 or 5. Only 8 in
+/// every 30 numbers get past that, so this does under a third of the divisions [crate::is_prime]
+/// does. It's still far too slow for big `n`.
+pub fn is_prime_wheel(n: u64) -> bool {
+    for p in [2, 3, 5] {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    if n < 2 {
+        return false;
+    }
+    // The gaps between the numbers from 7 on that aren't multiples of 2, 3 or 5
+    const GAPS: [u64; 8] = [4, 2, 4, 2, 4, 6, 2, 6];
+    let mut d = 7;
+    for &gap in GAPS.iter().cycle() {
+        if d > n / d {
+            return true;
+        }
+        if n.is_multiple_of(d) {
+            return false;
+        }
+        d += gap;
+    }
+    unreachable!()
+}
+
+/// The Jacobi symbol `(a/n)`, for odd `n`.
+fn jacobi(a: i64, n: u64) -> i32 {
+    let mut a = if a < 0 { n - (a.unsigned_abs() % n) } else { a as u64 % n };
+    let mut n = n;
+    let mut result = 1;
+    while a != 0 {
+        while a.is_multiple_of(2) {
+            a /= 2;
+            if n % 8 == 3 || n % 8 == 5 {
+                result = -result;
+            }
+        }
+        std::mem::swap(&mut a, &mut n);
+        if a % 4 == 3 && n % 4 == 3 {
+            result = -result;
+        }
+        a %= n;
+    }
+    if n == 1 {
+        result
+    } else {
+        0
+    }
+}
+
+/// `x / 2 (mod n)`, for odd `n`.
+fn half_mod(x: u64, n: u64) -> u64 {
+    if x.is_multiple_of(2) {
+        x / 2
+    } else {
+        ((x as u128 + n as u128) / 2) as u64
+    }
+}
+
+fn sub_mod(a: u64, b: u64, n: u64) -> u64 {
+    if a >= b {
+        a - b
+    } else {
+        n - (b - a)
+    }
+}
+
+/// The strong Lucas probable prime test, with parameters picked by Selfridge's method. `n` must be
+/// odd, and not a perfect square.
+fn strong_lucas(n: u64) -> bool {
+    // The first D in 5, -7, 9, -11, ... with (D/n) = -1
+    let mut d: i64 = 5;
+    loop {
+        match jacobi(d, n) {
+            -1 => break,
+            0 if d.unsigned_abs() != n => return false,
+            _ => d = if d > 0 { -(d + 2) } else { -d + 2 },
+        }
+    }
+    let to_mod = |x: i64| if x < 0 { n - (x.unsigned_abs() % n) } else { x as u64 % n };
+    let (big_d, q) = (to_mod(d), to_mod((1 - d) / 4));
+    // Write n + 1 as k * 2^s with k odd, then find U_k and V_k with P = 1
+    let n_plus_1 = n as u128 + 1;
+    let s = n_plus_1.trailing_zeros();
+    let k = n_plus_1 >> s;
+    let (mut u, mut v, mut q_k) = (1, 1, q);
+    for bit in (0..127 - k.leading_zeros()).rev() {
+        u = modular_multiplication(u, v, n);
+        v = sub_mod(modular_multiplication(v, v, n), modular_multiplication(2, q_k, n), n);
+        q_k = modular_multiplication(q_k, q_k, n);
+        if k & (1 << bit) != 0 {
+            let next_u = half_mod(((u as u128 + v as u128) % n as u128) as u64, n);
+            let next_v = half_mod(((modular_multiplication(big_d, u, n) as u128 + v as u128) % n as u128) as u64, n);
+            u = next_u;
+            v = next_v;
+            q_k = modular_multiplication(q_k, q, n);
+        }
+    }
+    if u == 0 || v == 0 {
+        return true;
+    }
+    for _ in 1..s {
+        v = sub_mod(modular_multiplication(v, v, n), modular_multiplication(2, q_k, n), n);
+        q_k = modular_multiplication(q_k, q_k, n);
+        if v == 0 {
+            return true;
+        }
+    }
+    false
+}
+
+/// The Baillie-PSW test: Miller-Rabin to base 2, then a strong Lucas test.
+///
+/// Nobody has ever found a composite number that passes both, and it's been checked for every
+/// number below 2^64, so for a `u64` this is as good as a proof.
+pub fn baillie_psw(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for p in [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    if !miller_rabin(n, &[2]) {
+        return false;
+    }
+    let root = n.isqrt();
+    root * root != n && strong_lucas(n)
+}
+
+/// Whether `n` is a safe prime: a prime `p` for which `(p - 1) / 2` is prime too.
+pub fn is_safe_prime(n: u64) -> bool {
+    n > 2 && is_prime_fast(n) && is_prime_fast((n - 1) / 2)
+}
+
+/// The `n`th safe prime, counting from 1: 5, 7, 11, 23, 47, ... Panics if `n` is 0.
+pub fn find_nth_safe_prime(n: u64) -> u64 {
+    assert!(n > 0, "There is no 0th safe prime");
+    PrimeIterator::starting_from(5)
+        .filter(|&p| is_safe_prime(p))
+        .nth((n - 1) as usize)
+        .expect("Ran out of safe primes below u64::MAX")
+}
+
+/// Search for the `n`th safe prime on a blocking thread, just like [crate::prime_output] does for
+/// the `n`th prime.
+pub async fn safe_prime_output(id: u64, n: u64) -> PrimeResult {
+    poll_fn(move |_| blocking(|| time_search(id, n, find_nth_safe_prime))).await.expect("Couldn't block")
+}
+
+/// Up to this many primes, [find_nth_prime_with_sieve_fallback] sieves for them.
+pub const SIEVE_THRESHOLD: u64 = 10_000;
+
+/// Past this many primes, [find_nth_prime_with_sieve_fallback] switches to a parallel sieve.
+pub const PARALLEL_THRESHOLD: u64 = 1_000_000;
+
+/// Find the `n`th prime (counting from 1) with whichever algorithm suits `n` best. Panics if `n`
+/// is 0.
+///
+/// * Up to [SIEVE_THRESHOLD], a plain sieve is quickest: the range is small enough that setting up
+///   anything cleverer costs more than it saves.
+/// * Up to [PARALLEL_THRESHOLD], we test each candidate with [is_prime_fast], which needs no
+///   memory to speak of.
+/// * Past that, we sieve segments of the range on several threads at once (see
+///   [find_nth_prime_threaded_sieve]).
+pub fn find_nth_prime_with_sieve_fallback(n: u64) -> u64 {
+    assert!(n > 0, "There is no 0th prime");
+    if n <= SIEVE_THRESHOLD {
+        first_primes(n as usize)[n as usize - 1]
+    } else if n <= PARALLEL_THRESHOLD {
+        PrimeIterator::starting_from(2).nth((n - 1) as usize).expect("Ran out of primes below u64::MAX")
+    } else {
+        find_nth_prime_threaded_sieve(n)
+    }
+}
+
+/// Run [find_nth_prime_with_sieve_fallback] on a blocking thread, so this has to be `spawn`ed
+/// onto the runtime.
+pub async fn find_nth_prime_auto(n: u64) -> u64 {
+    poll_fn(|_| blocking(|| find_nth_prime_with_sieve_fallback(n))).await.expect("Couldn't block")
+}
+
+/// [batch_is_prime] looks candidates below this up in a sieve.
+pub const BATCH_SIEVE_LIMIT: u64 = 1_000_000;
+
+/// Whether each number below [BATCH_SIEVE_LIMIT] is prime. It's sieved the first time it's needed,
+/// and kept for good.
+fn batch_sieve() -> &'static [bool] {
+    static SIEVE: OnceLock<Vec<bool>> = OnceLock::new();
+    SIEVE.get_or_init(|| {
+        let mut is_prime = vec![false; BATCH_SIEVE_LIMIT as usize];
+        for p in crate::sieve::sieve_primes(BATCH_SIEVE_LIMIT - 1) {
+            is_prime[p as usize] = true;
+        }
+        is_prime
+    })
+}
+
+/// The fewest of the [WITNESSES] that still make Miller-Rabin deterministic for `n`. Each bound is
+/// the smallest number that's a strong pseudoprime to every witness before it.
+fn witnesses_for(n: u64) -> &'static [u64] {
+    const BOUNDS: [(u64, usize); 8] = [
+        (2_047, 1),
+        (1_373_653, 2),
+        (25_326_001, 3),
+        (3_215_031_751, 4),
+        (2_152_302_898_747, 5),
+        (3_474_749_660_383, 6),
+        (341_550_071_728_321, 7),
+        (3_825_123_056_546_413_051, 9),
+    ];
+    let count = BOUNDS.iter().find(|&&(bound, _)| n < bound).map_or(WITNESSES.len(), |&(_, count)| count);
+    &WITNESSES[..count]
+}
+
+/// Whether each of the `candidates` is prime, in the same order.
+///
+/// This gives the same answers as calling [is_prime_fast] on each one, but saves work that only
+/// pays off across lots of calls: candidates below [BATCH_SIEVE_LIMIT] are looked up in a sieve
+/// that's built once and shared, and larger ones only use as many Miller-Rabin witnesses as their
+/// size needs, rather than all 12. Near 10^9 that's 4 witnesses.
+pub fn batch_is_prime(candidates: &[u64]) -> Vec<bool> {
+    let sieve = batch_sieve();
+    candidates
+        .iter()
+        .map(|&n| match sieve.get(n as usize) {
+            Some(&prime) if n < BATCH_SIEVE_LIMIT => prime,
+            _ => miller_rabin(n, witnesses_for(n)),
+        })
+        .collect()
+}
+
+/// Run [batch_is_prime] on a blocking thread, so this has to be `spawn`ed onto the runtime.
+pub async fn batch_is_prime_blocking(candidates: Vec<u64>) -> Vec<bool> {
+    poll_fn(|_| blocking(|| batch_is_prime(&candidates))).await.expect("Couldn't block")
+}
+
+/// A lower bound on the chance that Miller-Rabin with `iterations` random bases gets `n` right.
+///
+/// A composite passes any one random base with probability at most 1/4, so it passes all of them
+/// with probability at most `4^-iterations`. That holds whatever `n` is, so `n` doesn't change the
+/// answer, and for most composites the real chance of passing is far smaller.
+pub fn primality_confidence(_n: u64, iterations: u32) -> f64 {
+    1.0 - 4.0_f64.powi(-(iterations as i32))
+}
+
+/// Miller-Rabin with `iterations` bases picked at random from `2..=n-2` with `rng`. A `false`
+/// means `n` is definitely composite; see [primality_confidence] for how much to trust a `true`.
+pub fn miller_rabin_random(n: u64, iterations: u32, rng: &mut impl Rng) -> bool {
+    if n < 5 {
+        return n == 2 || n == 3;
+    }
+    if n.is_multiple_of(2) {
+        return false;
+    }
+    let witnesses = (0..iterations).map(|_| rng.gen_range(2..=n - 2)).collect::<Vec<_>>();
+    miller_rabin(n, &witnesses)
+}
+
+/// Whether `n` is prime, with a chance of getting it right of at least `confidence`.
+///
+/// Runs [miller_rabin_random] with as few random bases as [primality_confidence] says are needed.
+/// There's no number of random bases that makes it certain, so a `confidence` of 1 or more falls
+/// back to [is_prime_fast].
+pub fn is_probably_prime(n: u64, confidence: f64) -> bool {
+    if confidence >= 1.0 {
+        return is_prime_fast(n);
+    }
+    let iterations = ((1.0 - confidence).ln() / 0.25_f64.ln()).ceil().max(1.0) as u32;
+    miller_rabin_random(n, iterations, &mut rand::thread_rng())
+}
+
+/// Whether `n` is `a^b` for some `a` and some `b` of at least 2.
+fn is_perfect_power(n: u64) -> bool {
+    (2..u64::BITS).any(|b| {
+        let a = (n as f64).powf(1.0 / b as f64).round() as u64;
+        (a.saturating_sub(1)..=a + 1).any(|a| a >= 2 && a.checked_pow(b) == Some(n))
+    })
+}
+
+/// The smallest `r` for which the multiplicative order of `n` mod `r` is more than `limit`.
+fn aks_modulus(n: u64, limit: u64) -> u64 {
+    (2..)
+        .find(|&r| {
+            let mut x = 1;
+            gcd(n, r) == 1
+                && (1..=limit).all(|_| {
+                    x = modular_multiplication(x, n, r);
+                    x != 1
+                })
+        })
+        .expect("Ran out of moduli")
+}
+
+/// Euler's totient of `r`, by trial division.
+fn totient(mut r: u64) -> u64 {
+    let mut phi = r;
+    let mut p = 2;
+    while p * p <= r {
+        if r.is_multiple_of(p) {
+            phi -= phi / p;
+            while r.is_multiple_of(p) {
+                r /= p;
+            }
+        }
+        p += 1;
+    }
+    if r > 1 {
+        phi -= phi / r;
+    }
+    phi
+}
+
+/// `p * q` modulo `X^r - 1` and `n`, where `r` is the length of both. The sums are built up in
+/// `u128`s and only reduced once each, unless `n` is big enough that the products could overflow.
+fn polynomial_multiplication(p: &[u64], q: &[u64], n: u64) -> Vec<u64> {
+    let r = p.len();
+    let mut product = vec![0u128; r];
+    let small = n <= u32::MAX as u64;
+    let add = |c: &mut u128, a: u64, b: u64| {
+        let term = a as u128 * b as u128;
+        *c += if small { term } else { term % n as u128 };
+    };
+    for (i, &a) in p.iter().enumerate().filter(|&(_, &a)| a != 0) {
+        // X^i * X^j wraps round to X^(i + j - r) once i + j reaches r
+        for (c, &b) in product[i..].iter_mut().zip(q) {
+            add(c, a, b);
+        }
+        for (c, &b) in product[..i].iter_mut().zip(&q[r - i..]) {
+            add(c, a, b);
+        }
+    }
+    product.into_iter().map(|c| (c % n as u128) as u64).collect()
+}
+
+/// `(X + a)^n` modulo `X^r - 1` and `n`.
+fn polynomial_power(a: u64, n: u64, r: usize) -> Vec<u64> {
+    let mut result = vec![0; r];
+    result[0] = 1;
+    for bit in (0..u64::BITS - n.leading_zeros()).rev() {
+        result = polynomial_multiplication(&result, &result, n);
+        if (n >> bit) & 1 == 1 {
+            // Multiplying by X + a is a shift and an add, so there's no need for a full multiplication
+            let shifted = result.iter().cycle().skip(r - 1).take(r).copied().collect::<Vec<_>>();
+            for (c, x) in result.iter_mut().zip(shifted) {
+                *c = ((*c as u128 * a as u128 + x as u128) % n as u128) as u64;
+            }
+        }
+    }
+    result
+}
+
+/// The AKS test: Agrawal, Kayal and Saxena's proof that primality can be decided in polynomial
+/// time, in the simpler form from their paper.
+///
+/// Unlike every other test here, this needs no table of witnesses, no computer search to show
+/// those witnesses are enough, and no unproven conjecture: every step of the answer follows from
+/// the theorem in the paper. That's the only reason to use it. The polynomials have about
+/// `log2(n)^2` coefficients and it raises hundreds of them to the `n`th power, so on a prime it's
+/// hundreds of thousands of times slower than Miller-Rabin, and takes over a second by `10^6`. For
+/// any `u64`, deterministic Miller-Rabin has already been checked to be exactly right, and for
+/// numbers past that, real primality proofs use elliptic curve methods, which are much faster. So
+/// AKS is for checking other tests against, or for seeing how the proof works.
+pub fn is_prime_aks(n: u64) -> bool {
+    if n < 2 || is_perfect_power(n) {
+        return false;
+    }
+    let log = (n as f64).log2();
+    let r = aks_modulus(n, (log * log) as u64);
+    if (2..=r.min(n - 1)).any(|a| gcd(a, n) > 1) {
+        return false;
+    }
+    if n <= r {
+        return true;
+    }
+    // For a prime n, (X + a)^n = X^n + a, and it's enough to check that for the first few a
+    let limit = ((totient(r) as f64).sqrt() * log) as u64;
+    (1..=limit).all(|a| {
+        let mut expected = vec![0; r as usize];
+        expected[0] = a % n;
+        expected[(n % r) as usize] += 1;
+        polynomial_power(a, n, r as usize) == expected
+    })
+}
+
+/// The primality tests in this module, and [crate::is_prime], for picking one at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrimalityMethod {
+    /// [crate::is_prime]
+    TrialDivision,
+    /// [is_prime_wheel]
+    Wheel,
+    /// [is_prime_fast]
+    MillerRabin,
+    /// [baillie_psw]
+    BailliePsw,
+    /// [is_prime_aks]
+    Aks,
+}
+
+impl PrimalityMethod {
+    /// Test whether `n` is prime with this method.
+    pub fn is_prime(self, n: u64) -> bool {
+        match self {
+            PrimalityMethod::TrialDivision => crate::is_prime(n),
+            PrimalityMethod::Wheel => is_prime_wheel(n),
+            PrimalityMethod::MillerRabin => is_prime_fast(n),
+            PrimalityMethod::BailliePsw => baillie_psw(n),
+            PrimalityMethod::Aks => is_prime_aks(n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sieve::sieve_primes;
+    use crate::tables::OEIS_A000040_FIRST_1000;
+    use crate::test_vectors::{generate_prime_test_vectors, CARMICHAEL_NUMBERS, STRONG_PSEUDOPRIMES};
+
+    #[test]
+    fn bitset_holds_the_primes_below_65536() {
+        assert_eq!(SMALL_PRIMES_BITSET.iter().map(|w| w.count_ones()).sum::<u32>(), 6542);
+        let primes = sieve_primes(65535);
+        for n in 0..65536 {
+            assert_eq!(is_prime_lookup_table(n), Some(primes.binary_search(&n).is_ok()), "{}", n);
+        }
+        assert_eq!(is_prime_lookup_table(65537), None);
+    }
+
+    #[test]
+    fn kth_prime_from_a_start() {
+        assert_eq!(kth_prime_ge(1, 10), 11);
+        assert_eq!(kth_prime_ge(3, 10), 17);
+        assert_eq!(kth_prime_ge(1, 2), 2);
+        assert_eq!(kth_prime_lt(1, 10), Some(7));
+        assert_eq!(kth_prime_lt(5, 10), None);
+    }
+
+    #[test]
+    fn safe_primes() {
+        assert!(is_safe_prime(7));
+        assert!(is_safe_prime(11));
+        assert!(!is_safe_prime(13));
+        assert_eq!(find_nth_safe_prime(1), 5);
+        // The safe primes go 5, 7, 11, 23, 47, so 23 is the 4th
+        assert_eq!(find_nth_safe_prime(4), 23);
+        assert_eq!(find_nth_safe_prime(5), 47);
+    }
+
+    #[test]
+    fn every_path_agrees() {
+        for &(n, nth_prime) in &[(100, 541), (10_001, 104_743), (1_000_001, 15_485_867)] {
+            assert_eq!(find_nth_prime_with_sieve_fallback(n), nth_prime, "fallback, n = {}", n);
+            // Each path takes seconds past its own threshold in a debug build, so only run the
+            // others on the smaller `n`s
+            if n <= PARALLEL_THRESHOLD {
+                let iterated = PrimeIterator::starting_from(2).nth((n - 1) as usize);
+                assert_eq!(first_primes(n as usize)[n as usize - 1], nth_prime, "sieve, n = {}", n);
+                assert_eq!(iterated, Some(nth_prime), "iterator, n = {}", n);
+                assert_eq!(find_nth_prime_threaded_sieve(n), nth_prime, "threaded sieve, n = {}", n);
+            }
+        }
+        let auto = crate::prime_test! { crate::spawn_with_handle(find_nth_prime_auto(10_001)).await };
+        assert_eq!(auto, 104_743);
+    }
+
+    #[test]
+    fn batch_agrees_with_one_at_a_time() {
+        let mut candidates: Vec<u64> = (0..2000).collect();
+        candidates.extend(BATCH_SIEVE_LIMIT - 1000..BATCH_SIEVE_LIMIT + 1000);
+        candidates.extend((1_000_000_000..1_000_010_000).step_by(3));
+        candidates.extend(STRONG_PSEUDOPRIMES.iter());
+        candidates.extend(generate_prime_test_vectors().into_iter().map(|(n, _)| n));
+        let expected: Vec<_> = candidates.iter().map(|&n| is_prime_fast(n)).collect();
+        assert_eq!(batch_is_prime(&candidates), expected);
+        let blocking = crate::prime_test! { crate::spawn_with_handle(batch_is_prime_blocking(candidates)).await };
+        assert_eq!(blocking, expected);
+    }
+
+    #[test]
+    fn confidence_grows_with_the_bases() {
+        assert!((primality_confidence(7919, 10) - 0.999_999_046_3).abs() < 1e-10);
+        assert_eq!(primality_confidence(7919, 1), 0.75);
+        assert!(is_probably_prime(7919, 0.9999));
+        assert!(!is_probably_prime(7921, 0.9999));
+        assert!(is_probably_prime(7919, 1.0));
+    }
+
+    #[test]
+    fn random_bases_never_reject_a_prime_and_catch_pseudoprimes() {
+        use rand::{rngs::StdRng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(7919);
+        for p in OEIS_A000040_FIRST_1000.iter() {
+            assert!(miller_rabin_random(*p, 3, &mut rng), "{} was called composite", p);
+        }
+        for &n in STRONG_PSEUDOPRIMES.iter().chain(&CARMICHAEL_NUMBERS) {
+            assert!(!miller_rabin_random(n, 20, &mut rng), "{} was called prime", n);
+        }
+    }
+
+    #[test]
+    fn aks_agrees_with_the_sieve() {
+        // AKS only does all of its work on a prime, and near 10,000 that takes it about a quarter of
+        // a second each in a debug build. So we check every composite up to 10,000, and every prime
+        // below 500, but only a sample of the ones above that.
+        let primes = sieve_primes(10_000);
+        for n in 0..=10_000 {
+            if primes.binary_search(&n).is_err() {
+                assert!(!is_prime_aks(n), "{}", n);
+            }
+        }
+        let below_500 = primes.iter().take_while(|&&p| p < 500);
+        for &p in below_500.chain(primes.iter().step_by(100)).chain(primes.last()) {
+            assert!(is_prime_aks(p), "{}", p);
+        }
+        assert!(PrimalityMethod::Aks.is_prime(7_919));
+    }
+}
@@ -0,0 +1,69 @@
+//! Letting the user know we haven't hung while the blocking threads grind away.
+
+use futures::future::{select, Either};
+use futures::pin_mut;
+use futures::stream::StreamExt;
+use std::future::Future;
+use std::io::{self, Write};
+use std::time::Duration;
+use tokio::timer::Interval;
+
+/// A spinner drawn in place on the current terminal line.
+#[derive(Debug, Clone)]
+pub struct AnsiSpinner {
+    pub frames: &'static [&'static str],
+    /// How long each frame is shown for
+    pub interval: Duration,
+}
+
+impl Default for AnsiSpinner {
+    fn default() -> Self {
+        AnsiSpinner {
+            frames: &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+            interval: Duration::from_millis(80),
+        }
+    }
+}
+
+impl AnsiSpinner {
+    /// Draw the spinner next to `label` until `f` completes, then print `label done` and return
+    /// `f`'s output.
+    ///
+    /// The spinner only ever runs while this future is being polled, and it stops the moment `f`
+    /// is done, so it costs nothing once `f` has produced its value. `f` itself is polled exactly
+    /// as it would be without the spinner. Anything else that prints while the spinner is running
+    /// will end up on the spinner's line, though.
+    pub async fn spin_until<F: Future>(&self, label: &str, f: F) -> F::Output {
+        pin_mut!(f);
+        let mut ticks = Interval::new_interval(self.interval);
+        let mut frames = self.frames.iter().cycle();
+        loop {
+            match select(f.as_mut(), StreamExt::next(&mut ticks)).await {
+                Either::Left((output, _)) => {
+                    println!("\r{} done", label);
+                    return output;
+                }
+                Either::Right(_) => {
+                    print!("\r{} {}", label, frames.next().unwrap_or(&""));
+                    io::stdout().flush().ok();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn_prime_output;
+    use futures::future::ready;
+
+    #[test]
+    fn spinner_returns_the_value() {
+        crate::prime_test! {
+            let spinner = AnsiSpinner { interval: Duration::from_millis(1), ..AnsiSpinner::default() };
+            assert_eq!(spinner.spin_until("Searching", spawn_prime_output(0, 100_000)).await.value, 1_299_709);
+            assert_eq!(spinner.spin_until("Nothing to do", ready(42)).await, 42);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Racing different primality tests against each other on the same search.
+//!
+//! Every racer looks for the first `n` primes, testing candidates with its own algorithm on its
+//! own blocking thread. They all report to a shared [Leaderboard] each time they find a prime, and
+//! anyone who wants a running commentary can [subscribe](AlgorithmRace::subscribe) to the
+//! updates.
+
+use crate::events::EventBus;
+use crate::primality::{is_prime_wheel, miller_rabin, WITNESSES};
+use crate::spawn_with_handle;
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::future::{join_all, poll_fn};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_executor::threadpool::blocking;
+
+/// A named primality test.
+#[derive(Debug, Clone, Copy)]
+pub struct Algorithm {
+    pub name: &'static str,
+    pub is_prime: fn(u64) -> bool,
+}
+
+/// The three algorithms the demo races: [crate::is_prime], [is_prime_wheel] and deterministic
+/// [miller_rabin].
+pub fn default_algorithms() -> Vec<Algorithm> {
+    vec![
+        Algorithm { name: "Trial division", is_prime: crate::is_prime },
+        Algorithm { name: "Wheel", is_prime: is_prime_wheel },
+        Algorithm { name: "Miller-Rabin", is_prime: |n| miller_rabin(n, &WITNESSES) },
+    ]
+}
+
+/// How one racer is getting on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+    pub algorithm: &'static str,
+    /// How many primes it has found so far
+    pub found: u64,
+    /// The last prime it found
+    pub latest: u64,
+    /// How long it took to find all `n` primes, once it has
+    pub finished_after: Option<Duration>,
+}
+
+/// Every racer's progress, in the order the algorithms were entered.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Leaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+/// Sent to subscribers every time a racer finds a prime.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderboardUpdate {
+    pub algorithm: &'static str,
+    pub found: u64,
+    pub latest: u64,
+}
+
+/// A race between `algorithms` to find the first `n` primes.
+pub struct AlgorithmRace {
+    algorithms: Vec<Algorithm>,
+    board: Arc<Mutex<Leaderboard>>,
+    updates: Arc<EventBus<LeaderboardUpdate>>,
+}
+
+impl AlgorithmRace {
+    pub fn new(algorithms: Vec<Algorithm>) -> Self {
+        AlgorithmRace { algorithms, board: Arc::default(), updates: Arc::new(EventBus::new()) }
+    }
+
+    /// The live leaderboard. It's updated every time any racer finds a prime.
+    pub fn leaderboard(&self) -> Arc<Mutex<Leaderboard>> {
+        self.board.clone()
+    }
+
+    /// A stream of every update from now on.
+    pub fn subscribe(&self) -> UnboundedReceiver<LeaderboardUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Race every algorithm to the `n`th prime, and return the final leaderboard.
+    ///
+    /// Each racer gets a blocking thread to itself, so give the runtime at least as many blocking
+    /// threads as there are racers, or the late starters will be handicapped.
+    pub async fn run(&self, n: u64) -> Leaderboard {
+        let entries = self
+            .algorithms
+            .iter()
+            .map(|a| LeaderboardEntry { algorithm: a.name, found: 0, latest: 0, finished_after: None })
+            .collect();
+        *self.board.lock().unwrap() = Leaderboard { entries };
+        let start = Instant::now();
+        let racers = self.algorithms.iter().copied().enumerate().map(|(place, algorithm)| {
+            let (board, updates) = (self.board.clone(), self.updates.clone());
+            spawn_with_handle(async move {
+                poll_fn(|_| blocking(|| race(algorithm, place, n, start, &board, &updates))).await.expect("Couldn't block")
+            })
+        });
+        join_all(racers.collect::<Vec<_>>()).await;
+        self.board.lock().unwrap().clone()
+    }
+}
+
+fn race(
+    algorithm: Algorithm,
+    place: usize,
+    n: u64,
+    start: Instant,
+    board: &Mutex<Leaderboard>,
+    updates: &EventBus<LeaderboardUpdate>,
+) {
+    let mut found = 0;
+    let mut candidate = 1;
+    while found < n {
+        candidate += 1;
+        if (algorithm.is_prime)(candidate) {
+            found += 1;
+            let entry = &mut board.lock().unwrap().entries[place];
+            entry.found = found;
+            entry.latest = candidate;
+            updates.publish(LeaderboardUpdate { algorithm: algorithm.name, found, latest: candidate });
+        }
+    }
+    board.lock().unwrap().entries[place].finished_after = Some(start.elapsed());
+}
+
+/// Lay the leaderboard out as a table, leaders first. Finished racers are ranked by how long they
+/// took, and the rest by how many primes they've found.
+pub fn format_leaderboard(board: &Leaderboard) -> String {
+    let mut entries = board.entries.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b| match (a.finished_after, b.finished_after) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => b.found.cmp(&a.found),
+    });
+    let mut table = format!("{:>2}  {:<16}  {:>8}  {:>10}  {:>8}\n", "", "Algorithm", "Found", "Latest", "Time (s)");
+    table.push_str(&format!("{:-<2}  {:-<16}  {:-<8}  {:-<10}  {:-<8}\n", "", "", "", "", ""));
+    for (rank, e) in entries.iter().enumerate() {
+        let time = e.finished_after.map(|t| format!("{:.3}", t.as_secs_f64())).unwrap_or_default();
+        let line = format!("{:>2}  {:<16}  {:>8}  {:>10}  {:>8}", rank + 1, e.algorithm, e.found, e.latest, time);
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// Print the leaderboard as a table.
+pub fn print_leaderboard(board: &Leaderboard) {
+    print!("{}", format_leaderboard(board));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_racer_finds_all_the_primes() {
+        crate::prime_test! {
+            let board = AlgorithmRace::new(default_algorithms()).run(1000).await;
+            assert_eq!(board.entries.len(), 3);
+            for entry in board.entries {
+                assert_eq!(entry.found, 1000, "{} didn't finish", entry.algorithm);
+                assert_eq!(entry.latest, 7919, "{} finished on the wrong prime", entry.algorithm);
+                assert!(entry.finished_after.is_some());
+            }
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! Prime searches that survive the odd failure.
+//!
+//! A search can fail because `blocking` has no thread pool to hand the work to, or because the
+//! search itself panics. Rather than losing the whole batch, each failed search is retried a few
+//! times, backing off a little longer after each failure, before we give up on just that search.
+//!
+//! If searches keep failing, though, something is probably wrong with all of them, and retrying
+//! just piles on more work. A [CircuitBreaker] counts the failures, and once there are too many in
+//! a row it stops any more attempts from starting. After a cooldown it lets a single attempt
+//! through to see if things have recovered.
+
+use crate::{try_prime_output, PrimeResult};
+use futures::future::{join_all, FutureExt};
+use std::any::Any;
+use std::fmt::Display;
+use std::future::Future;
+use std::fmt;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long to wait before the first retry. The wait doubles after every failed attempt.
+pub const INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+
+/// How long an open [CircuitBreaker] made by [run_pipeline_with_circuit_breaker] waits before it
+/// lets another attempt through.
+pub const CIRCUIT_COOLDOWN: Duration = Duration::from_millis(100);
+
+/// How many times [run_pipeline_with_circuit_breaker] retries each search.
+pub const PIPELINE_MAX_RETRIES: u32 = 3;
+
+/// Where a [CircuitBreaker] is up to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CircuitState {
+    /// Everything is fine, and attempts go ahead as normal.
+    Closed,
+    /// There have been too many failures, and no attempts are allowed until the cooldown is up.
+    Open,
+    /// The cooldown is up. The next attempt is a trial: if it succeeds the breaker closes, and if
+    /// it fails the breaker opens again.
+    HalfOpen,
+}
+
+const CLOSED: u8 = 0;
+const OPEN: u8 = 1;
+const HALF_OPEN: u8 = 2;
+
+/// The error for an attempt that the [CircuitBreaker] wouldn't let start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircuitOpen;
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the circuit breaker is open")
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+/// Stops new attempts once `threshold` of them have failed in a row.
+///
+/// Ask [CircuitBreaker::try_acquire] before starting each attempt, then report how it went with
+/// [CircuitBreaker::record_success] or [CircuitBreaker::record_failure]. The cooldown is checked
+/// whenever someone asks, so a breaker doesn't need a runtime, or a timer, to move on from
+/// [CircuitState::Open].
+pub struct CircuitBreaker {
+    /// Failures in a row while closed
+    failures: AtomicU32,
+    threshold: u32,
+    state: AtomicU8,
+    cooldown: Duration,
+    opened_at: Mutex<Option<Instant>>,
+    /// Whether the half open breaker's one trial attempt has been handed out
+    trial_started: AtomicBool,
+}
+
+impl CircuitBreaker {
+    /// A closed breaker that opens after `threshold` failures in a row, and half opens `cooldown`
+    /// after that. A threshold of 0 is treated as 1.
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            failures: AtomicU32::new(0),
+            threshold: threshold.max(1),
+            state: AtomicU8::new(CLOSED),
+            cooldown,
+            opened_at: Mutex::new(None),
+            trial_started: AtomicBool::new(false),
+        }
+    }
+
+    /// The breaker's state right now, moving it on to [CircuitState::HalfOpen] if it's open and
+    /// the cooldown is up.
+    pub fn state(&self) -> CircuitState {
+        if self.state.load(Ordering::SeqCst) == OPEN {
+            let opened_at = self.opened_at.lock().unwrap();
+            // Someone else may have half opened the breaker (and handed out its trial) while we
+            // waited for the lock, so check again now that nobody else can change the state
+            let open = self.state.load(Ordering::SeqCst) == OPEN;
+            if open && opened_at.is_some_and(|t| t.elapsed() >= self.cooldown) {
+                self.trial_started.store(false, Ordering::SeqCst);
+                self.state.store(HALF_OPEN, Ordering::SeqCst);
+            }
+        }
+        match self.state.load(Ordering::SeqCst) {
+            CLOSED => CircuitState::Closed,
+            OPEN => CircuitState::Open,
+            _ => CircuitState::HalfOpen,
+        }
+    }
+
+    /// How many attempts have failed in a row since the breaker last closed.
+    pub fn failures(&self) -> u32 {
+        self.failures.load(Ordering::SeqCst)
+    }
+
+    /// Whether an attempt may start. A closed breaker always says yes, an open one always says
+    /// no, and a half open one says yes exactly once.
+    pub fn try_acquire(&self) -> Result<(), CircuitOpen> {
+        match self.state() {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open => Err(CircuitOpen),
+            CircuitState::HalfOpen => match self.trial_started.swap(true, Ordering::SeqCst) {
+                false => Ok(()),
+                true => Err(CircuitOpen),
+            },
+        }
+    }
+
+    /// Report that an attempt succeeded. This closes a half open breaker.
+    pub fn record_success(&self) {
+        let mut opened_at = self.opened_at.lock().unwrap();
+        if self.state.load(Ordering::SeqCst) != OPEN {
+            self.failures.store(0, Ordering::SeqCst);
+            self.state.store(CLOSED, Ordering::SeqCst);
+            *opened_at = None;
+        }
+    }
+
+    /// Report that an attempt failed. This opens a half open breaker straight away, and a closed
+    /// one once this is the `threshold`th failure in a row.
+    pub fn record_failure(&self) {
+        let mut opened_at = self.opened_at.lock().unwrap();
+        let failures = self.failures.fetch_add(1, Ordering::SeqCst) + 1;
+        let state = self.state.load(Ordering::SeqCst);
+        if state == HALF_OPEN || (state == CLOSED && failures >= self.threshold) {
+            self.state.store(OPEN, Ordering::SeqCst);
+            *opened_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Run all the `(id, n)` searches concurrently, retrying each one up to `max_retries` times if it
+/// fails. The results come back in the same order as `tasks`, with an `Err` describing the last
+/// failure for any search that never succeeded.
+pub async fn run_with_retry(tasks: Vec<(u64, u64)>, max_retries: u32) -> Vec<Result<PrimeResult, String>> {
+    run_with_retry_using(tasks, max_retries, try_prime_output).await
+}
+
+/// The same as [run_with_retry], but `search` produces the future for each attempt, so you can
+/// swap in something other than [try_prime_output]. Panics in the search are caught and count as
+/// failures.
+pub async fn run_with_retry_using<F, Fut, E>(
+    tasks: Vec<(u64, u64)>,
+    max_retries: u32,
+    search: F,
+) -> Vec<Result<PrimeResult, String>>
+where
+    F: Fn(u64, u64) -> Fut,
+    Fut: Future<Output = Result<PrimeResult, E>> + Send + 'static,
+    E: Display + Send + 'static,
+{
+    let searches = tasks.into_iter().map(|(id, n)| retry(id, n, max_retries, None, &search));
+    join_all(searches).await
+}
+
+/// Run all the `(id, n)` searches like [run_with_retry] does, but stop starting new attempts once
+/// `failure_threshold` of them have failed in a row.
+///
+/// Each search is retried up to [PIPELINE_MAX_RETRIES] times. Any search that wants to start an
+/// attempt while the breaker is open fails straight away instead, without waiting for it to close.
+pub async fn run_pipeline_with_circuit_breaker(
+    tasks: Vec<(u64, u64)>,
+    failure_threshold: u32,
+) -> Vec<Result<PrimeResult, String>> {
+    let breaker = CircuitBreaker::new(failure_threshold, CIRCUIT_COOLDOWN);
+    run_with_circuit_breaker_using(tasks, PIPELINE_MAX_RETRIES, &breaker, try_prime_output).await
+}
+
+/// The same as [run_with_retry_using], but every attempt has to get past `breaker` first, and
+/// reports back to it how it went.
+pub async fn run_with_circuit_breaker_using<F, Fut, E>(
+    tasks: Vec<(u64, u64)>,
+    max_retries: u32,
+    breaker: &CircuitBreaker,
+    search: F,
+) -> Vec<Result<PrimeResult, String>>
+where
+    F: Fn(u64, u64) -> Fut,
+    Fut: Future<Output = Result<PrimeResult, E>> + Send + 'static,
+    E: Display + Send + 'static,
+{
+    let searches = tasks.into_iter().map(|(id, n)| retry(id, n, max_retries, Some(breaker), &search));
+    join_all(searches).await
+}
+
+async fn retry<F, Fut, E>(
+    id: u64,
+    n: u64,
+    max_retries: u32,
+    breaker: Option<&CircuitBreaker>,
+    search: &F,
+) -> Result<PrimeResult, String>
+where
+    F: Fn(u64, u64) -> Fut,
+    Fut: Future<Output = Result<PrimeResult, E>> + Send + 'static,
+    E: Display + Send + 'static,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        if let Some(Err(e)) = breaker.map(CircuitBreaker::try_acquire) {
+            return Err(format!("#{} failed after {} attempts: {}", id, attempt, e));
+        }
+        // Each attempt is its own task so that a panic only takes down that attempt. The handle
+        // re-raises the panic when we await it, which is where we catch it.
+        let (task, handle) = search(id, n).remote_handle();
+        tokio::spawn(task);
+        let error = match AssertUnwindSafe(handle).catch_unwind().await {
+            Ok(Ok(result)) => {
+                if let Some(breaker) = breaker {
+                    breaker.record_success();
+                }
+                return Ok(result);
+            }
+            Ok(Err(e)) => e.to_string(),
+            Err(panic) => panic_message(panic.as_ref()),
+        };
+        if let Some(breaker) = breaker {
+            breaker.record_failure();
+        }
+        if attempt == max_retries {
+            return Err(format!("#{} failed after {} attempts: {}", id, attempt + 1, error));
+        }
+        attempt += 1;
+        tokio::timer::delay(tokio::clock::now() + backoff).await;
+        backoff *= 2;
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(s) = panic.downcast_ref::<&str>() {
+        format!("panicked: {}", s)
+    } else if let Some(s) = panic.downcast_ref::<String>() {
+        format!("panicked: {}", s)
+    } else {
+        "panicked".to_string()
+    }
+}
+
+/// Print how many searches succeeded and failed, along with the reason for each failure.
+pub fn summarize_results(results: &[Result<PrimeResult, String>]) {
+    let failures = results.iter().filter_map(|r| r.as_ref().err()).collect::<Vec<_>>();
+    println!("{} succeeded, {} failed", results.len() - failures.len(), failures.len());
+    for failure in failures {
+        println!("  {}", failure);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn retries_until_the_search_stops_panicking() {
+        crate::prime_test! {
+            let attempts = Arc::new(AtomicU32::new(0));
+            let search = |id, n| {
+                let attempts = attempts.clone();
+                async move {
+                    if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                        panic!("Injected failure");
+                    }
+                    try_prime_output(id, n).await
+                }
+            };
+            let results = run_with_retry_using(vec![(0, 10)], 3, search).await;
+            assert_eq!(results[0].as_ref().map(|r| r.value), Ok(29));
+            assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        }
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        crate::prime_test! {
+            let search = |_, _| async { Err::<PrimeResult, _>("Injected failure") };
+            let results = run_with_retry_using(vec![(7, 10)], 2, search).await;
+            assert_eq!(results, vec![Err("#7 failed after 3 attempts: Injected failure".to_string())]);
+        }
+    }
+
+    #[test]
+    fn repeated_panics_open_the_breaker() {
+        crate::prime_test! {
+            let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+            let attempts = Arc::new(AtomicU32::new(0));
+            let search = |id, n| {
+                let attempts = attempts.clone();
+                async move {
+                    // Far more failures than the breaker will ever let through
+                    if attempts.fetch_add(1, Ordering::SeqCst) < 100 {
+                        panic!("Injected failure");
+                    }
+                    try_prime_output(id, n).await
+                }
+            };
+            let results = run_with_circuit_breaker_using(vec![(0, 10)], 5, &breaker, search).await;
+            assert_eq!(results, vec![Err("#0 failed after 3 attempts: the circuit breaker is open".to_string())]);
+            assert_eq!(attempts.load(Ordering::SeqCst), 3);
+            assert_eq!(breaker.state(), CircuitState::Open);
+        }
+    }
+
+    #[test]
+    fn breaker_half_opens_after_the_cooldown() {
+        let cooldown = Duration::from_millis(20);
+        let breaker = CircuitBreaker::new(2, cooldown);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(breaker.try_acquire(), Err(CircuitOpen));
+
+        // A failed trial opens it again straight away
+        std::thread::sleep(cooldown);
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(breaker.try_acquire(), Ok(()));
+        assert_eq!(breaker.try_acquire(), Err(CircuitOpen));
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // And a successful one closes it
+        std::thread::sleep(cooldown);
+        assert_eq!(breaker.try_acquire(), Ok(()));
+        breaker.record_success();
+        assert_eq!((breaker.state(), breaker.failures()), (CircuitState::Closed, 0));
+    }
+
+    #[test]
+    fn half_open_breaker_admits_exactly_one_trial() {
+        for _ in 0..200 {
+            let breaker = CircuitBreaker::new(1, Duration::from_millis(0));
+            breaker.record_failure();
+            let start = std::sync::Barrier::new(4);
+            let admitted = std::thread::scope(|scope| {
+                let threads = (0..4)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            start.wait();
+                            (0..50).filter(|_| breaker.try_acquire().is_ok()).count()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                threads.into_iter().map(|t| t.join().unwrap()).sum::<usize>()
+            });
+            assert_eq!(admitted, 1);
+        }
+    }
+}
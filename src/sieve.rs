@@ -0,0 +1,222 @@
+//! The sieve of Eratosthenes. It needs a byte of memory for every number up to the limit, but it's
+//! enormously faster than testing each number on its own, which makes it a good source of known
+//! good answers to check the slow searches against.
+//!
+//! There are a few variations on it here, each behind the [Sieve] trait so they can stand in for
+//! each other.
+
+/// All the primes less than or equal to `limit`, in order.
+pub fn sieve_primes(limit: u64) -> Vec<u64> {
+    if limit < 2 {
+        return Vec::new();
+    }
+    let limit = limit as usize;
+    let mut is_prime = vec![true; limit + 1];
+    is_prime[0] = false;
+    is_prime[1] = false;
+    let mut p = 2;
+    while p * p <= limit {
+        if is_prime[p] {
+            for multiple in (p * p..=limit).step_by(p) {
+                is_prime[multiple] = false;
+            }
+        }
+        p += 1;
+    }
+    is_prime.iter().enumerate().filter(|(_, &prime)| prime).map(|(i, _)| i as u64).collect()
+}
+
+/// All the primes `p` with `low <= p < high`, in order.
+///
+/// This only needs memory for the numbers from `low` to `high`, plus the primes up to `√high` to
+/// cross them off with, so it works a long way past where [sieve_primes] runs out of memory. Those
+/// primes still add up near the top of the `u64` range, though: there are about 200 million primes
+/// below `√u64::MAX`.
+pub fn segmented_sieve(low: u64, high: u64) -> Vec<u64> {
+    let low = low.max(2);
+    if low >= high {
+        return Vec::new();
+    }
+    let mut is_prime = vec![true; (high - low) as usize];
+    for p in sieve_primes((high - 1).isqrt()) {
+        // The first multiple of p in the segment that isn't p itself
+        let first = (p * p).max(low.div_ceil(p) * p);
+        for multiple in (first..high).step_by(p as usize) {
+            is_prime[(multiple - low) as usize] = false;
+        }
+    }
+    is_prime.iter().enumerate().filter(|(_, &prime)| prime).map(|(i, _)| low + i as u64).collect()
+}
+
+/// The first `count` primes. We don't know in advance how far we need to sieve to find them, so
+/// we keep doubling the limit until we have enough.
+pub fn first_primes(count: usize) -> Vec<u64> {
+    let mut limit = 64;
+    loop {
+        let mut primes = sieve_primes(limit);
+        if primes.len() >= count {
+            primes.truncate(count);
+            return primes;
+        }
+        limit *= 2;
+    }
+}
+
+/// Something that can find all the primes up to a limit. Code that needs a list of primes can take
+/// any `Sieve`, so the sieve can be swapped for a faster, smaller or fake one.
+pub trait Sieve: Send + Sync {
+    /// All the primes less than or equal to `limit`, in order.
+    fn primes_up_to(&self, limit: u64) -> Vec<u64>;
+
+    /// How many primes there are up to and including `limit`.
+    fn count_up_to(&self, limit: u64) -> u64 {
+        self.primes_up_to(limit).len() as u64
+    }
+}
+
+/// The plain sieve of Eratosthenes in [sieve_primes], with a byte for every number.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EratosthenesSieve;
+
+impl Sieve for EratosthenesSieve {
+    fn primes_up_to(&self, limit: u64) -> Vec<u64> {
+        sieve_primes(limit)
+    }
+}
+
+/// The sieve of Eratosthenes with a bit, rather than a byte, for every odd number: a sixteenth of
+/// the memory of [EratosthenesSieve].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BitSieve;
+
+impl BitSieve {
+    /// Bit `i` is set if `2i + 1` is composite (or is 1).
+    fn composites(limit: u64) -> Vec<u64> {
+        let odds = limit.div_ceil(2) + 1;
+        let mut words = vec![0u64; odds.div_ceil(64) as usize];
+        words[0] |= 1;
+        let mut p = 3;
+        while p * p <= limit {
+            if words[(p / 2 / 64) as usize] & (1 << (p / 2 % 64)) == 0 {
+                for multiple in (p * p..=limit).step_by(2 * p as usize) {
+                    words[(multiple / 2 / 64) as usize] |= 1 << (multiple / 2 % 64);
+                }
+            }
+            p += 2;
+        }
+        words
+    }
+
+    /// Whether `n`, an odd number no more than the limit `composites` was sieved to, is prime.
+    fn is_odd_prime(composites: &[u64], n: u64) -> bool {
+        composites[(n / 2 / 64) as usize] & (1 << (n / 2 % 64)) == 0
+    }
+}
+
+impl Sieve for BitSieve {
+    fn primes_up_to(&self, limit: u64) -> Vec<u64> {
+        if limit < 2 {
+            return Vec::new();
+        }
+        let composites = BitSieve::composites(limit);
+        let odd_primes = (3..=limit).step_by(2).filter(|&n| BitSieve::is_odd_prime(&composites, n));
+        std::iter::once(2).chain(odd_primes).collect()
+    }
+
+    fn count_up_to(&self, limit: u64) -> u64 {
+        if limit < 2 {
+            return 0;
+        }
+        let composites = BitSieve::composites(limit);
+        1 + (3..=limit).step_by(2).filter(|&n| BitSieve::is_odd_prime(&composites, n)).count() as u64
+    }
+}
+
+/// [segmented_sieve] over `segment_size` numbers at a time, so the memory it needs beyond the
+/// primes themselves doesn't grow with the limit.
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentedSieve {
+    pub segment_size: u64,
+}
+
+impl Default for SegmentedSieve {
+    fn default() -> Self {
+        SegmentedSieve { segment_size: 1 << 16 }
+    }
+}
+
+impl Sieve for SegmentedSieve {
+    fn primes_up_to(&self, limit: u64) -> Vec<u64> {
+        let step = self.segment_size.max(1);
+        let mut primes = Vec::new();
+        let mut low = 0;
+        while low <= limit {
+            let high = low.saturating_add(step).min(limit.saturating_add(1));
+            primes.extend(segmented_sieve(low, high));
+            if high == limit.saturating_add(1) {
+                break;
+            }
+            low = high;
+        }
+        primes
+    }
+}
+
+/// A sieve that doesn't sieve at all: it just hands back whichever of the primes it was made with
+/// are small enough. Handy for testing code that takes a [Sieve] without waiting for a real one,
+/// or for checking it copes when the sieve gets something wrong.
+#[cfg(any(test, feature = "test-helpers"))]
+#[derive(Debug, Clone, Default)]
+pub struct MockSieve {
+    pub primes: Vec<u64>,
+}
+
+#[cfg(any(test, feature = "test-helpers"))]
+impl MockSieve {
+    pub fn new(primes: Vec<u64>) -> Self {
+        MockSieve { primes }
+    }
+}
+
+#[cfg(any(test, feature = "test-helpers"))]
+impl Sieve for MockSieve {
+    fn primes_up_to(&self, limit: u64) -> Vec<u64> {
+        self.primes.iter().copied().filter(|&p| p <= limit).collect()
+    }
+}
+
+/// All the primes `p` with `low <= p < high`, found with `sieve`.
+pub fn find_primes_with_sieve<S: Sieve>(low: u64, high: u64, sieve: &S) -> Vec<u64> {
+    if low >= high {
+        return Vec::new();
+    }
+    sieve.primes_up_to(high - 1).into_iter().filter(|&p| p >= low).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const PRIMES_UP_TO_100: [u64; 25] =
+        [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97];
+
+    #[rstest]
+    #[case::eratosthenes(&EratosthenesSieve)]
+    #[case::bits(&BitSieve)]
+    #[case::segmented(&SegmentedSieve { segment_size: 16 })]
+    #[case::mock(&MockSieve::new(PRIMES_UP_TO_100.to_vec()))]
+    fn every_sieve_finds_the_primes_up_to_100(#[case] sieve: &dyn Sieve) {
+        assert_eq!(sieve.primes_up_to(100), PRIMES_UP_TO_100);
+        assert_eq!(sieve.count_up_to(100), 25);
+        assert_eq!(sieve.primes_up_to(97).last(), Some(&97));
+        assert!(sieve.primes_up_to(1).is_empty());
+    }
+
+    #[test]
+    fn sieves_can_be_swapped_in() {
+        assert_eq!(find_primes_with_sieve(10, 30, &BitSieve), vec![11, 13, 17, 19, 23, 29]);
+        assert_eq!(find_primes_with_sieve(10, 30, &MockSieve::new(vec![2, 15, 29, 31])), vec![15, 29]);
+        assert!(find_primes_with_sieve(30, 10, &EratosthenesSieve).is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+//! Remembering which stretches of numbers have already been sieved.
+//!
+//! Ask for the primes from 1,000,000 to 2,000,000 and then for the primes from 1,500,000 to
+//! 2,500,000, and a plain sieve does the 1,500,000 to 2,000,000 part twice. [SieveCache] keeps
+//! every segment it sieves, and only sieves the parts of a range that no earlier request covered.
+
+use crate::sieve::segmented_sieve;
+use futures::future::poll_fn;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio_executor::threadpool::blocking;
+
+/// The primes `p` with `low <= p < high`, keyed by `(low, high)`.
+type Segments = BTreeMap<(u64, u64), Vec<u64>>;
+
+/// A shared cache of sieved segments. Clones share the same cache.
+#[derive(Debug, Clone, Default)]
+pub struct SieveCache {
+    /// The segments never overlap
+    segments: Arc<RwLock<Segments>>,
+    /// How many numbers we've sieved, counting each one every time it's sieved
+    sieved: Arc<AtomicU64>,
+}
+
+impl SieveCache {
+    pub fn new() -> Self {
+        SieveCache::default()
+    }
+
+    /// How many numbers this cache has had to sieve so far. Each number is only counted again if
+    /// two requests raced to sieve it at the same time.
+    pub fn numbers_sieved(&self) -> u64 {
+        self.sieved.load(Ordering::SeqCst)
+    }
+
+    /// The stretches of `[low, high)` that no cached segment covers.
+    fn gaps(&self, low: u64, high: u64) -> Vec<(u64, u64)> {
+        gaps(&self.segments.read().expect("The sieve cache lock was poisoned"), low, high)
+    }
+
+    /// Cache the `primes` we sieved from `[low, high)`. Someone else may have sieved some of that
+    /// while we were busy, so we keep theirs, and only add the parts that are still missing. That
+    /// way the segments never overlap, and none of `[low, high)` is left out.
+    fn insert(&self, low: u64, high: u64, primes: Vec<u64>) {
+        let mut segments = self.segments.write().expect("The sieve cache lock was poisoned");
+        for (lo, hi) in gaps(&segments, low, high) {
+            let start = primes.partition_point(|&p| p < lo);
+            let end = primes.partition_point(|&p| p < hi);
+            segments.insert((lo, hi), primes[start..end].to_vec());
+        }
+    }
+
+    /// The primes `p` with `low <= p < high`, sieving whatever parts of the range aren't already
+    /// cached. The sieving happens on a blocking thread, so this has to be `spawn`ed onto the
+    /// runtime.
+    pub async fn get_or_compute(&self, low: u64, high: u64) -> Vec<u64> {
+        for (lo, hi) in self.gaps(low, high) {
+            let primes = poll_fn(|_| blocking(|| segmented_sieve(lo, hi))).await.expect("Couldn't block");
+            self.sieved.fetch_add(hi - lo, Ordering::SeqCst);
+            self.insert(lo, hi, primes);
+        }
+        let segments = self.segments.read().expect("The sieve cache lock was poisoned");
+        segments
+            .range(..(high, 0))
+            .flat_map(|(_, primes)| primes.iter().copied())
+            .filter(|&p| p >= low && p < high)
+            .collect()
+    }
+}
+
+/// The stretches of `[low, high)` that none of `segments` covers.
+fn gaps(segments: &Segments, low: u64, high: u64) -> Vec<(u64, u64)> {
+    let mut gaps = Vec::new();
+    let mut cursor = low;
+    for &(lo, hi) in segments.range(..(high, 0)).map(|(range, _)| range).filter(|&&(_, hi)| hi > low) {
+        if lo > cursor {
+            gaps.push((cursor, lo));
+        }
+        cursor = cursor.max(hi);
+    }
+    if cursor < high {
+        gaps.push((cursor, high));
+    }
+    gaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn_with_handle;
+
+    #[test]
+    fn overlap_is_only_sieved_once() {
+        crate::prime_test! {
+            let cache = SieveCache::new();
+            let first = spawn_with_handle({
+                let cache = cache.clone();
+                async move { cache.get_or_compute(100_000, 200_000).await }
+            });
+            assert_eq!(first.await, segmented_sieve(100_000, 200_000));
+            let second = spawn_with_handle({
+                let cache = cache.clone();
+                async move { cache.get_or_compute(150_000, 250_000).await }
+            });
+            assert_eq!(second.await, segmented_sieve(150_000, 250_000));
+            // 100,000 for the first request, and only the 50,000 it didn't cover for the second
+            assert_eq!(cache.numbers_sieved(), 150_000);
+        }
+    }
+
+    #[test]
+    fn a_segment_that_lost_a_race_keeps_what_the_winner_missed() {
+        let cache = SieveCache::new();
+        cache.insert(0, 100, segmented_sieve(0, 100));
+        cache.insert(50, 150, segmented_sieve(50, 150));
+        assert_eq!(cache.gaps(0, 150), vec![]);
+        let segments = cache.segments.read().unwrap();
+        assert_eq!(segments.keys().copied().collect::<Vec<_>>(), vec![(0, 100), (100, 150)]);
+        assert_eq!(segments[&(100, 150)], segmented_sieve(100, 150));
+    }
+
+    #[test]
+    fn overlapping_requests_at_the_same_time() {
+        crate::prime_test! {
+            for _ in 0..10 {
+                let cache = SieveCache::new();
+                let (first, second) = (cache.clone(), cache.clone());
+                let first = spawn_with_handle(async move { first.get_or_compute(0, 100_000).await });
+                let second = spawn_with_handle(async move { second.get_or_compute(50_000, 150_000).await });
+                let (first, second) = futures::future::join(first, second).await;
+                assert_eq!(first, segmented_sieve(0, 100_000));
+                assert_eq!(second, segmented_sieve(50_000, 150_000));
+                assert_eq!(cache.get_or_compute(0, 150_000).await, segmented_sieve(0, 150_000));
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Putting a time limit on each search.
+//!
+//! Each search is spawned with [crate::spawn_with_handle], and when it runs over, we drop its
+//! `RemoteHandle`, pick one of the [TimeoutAction]s, and move on. What happens to the search then
+//! depends on how far it got:
+//!
+//! * If it was still waiting for a blocking thread, dropping the handle cancels it, and it never
+//!   runs at all.
+//! * If it already had a blocking thread, it can't be stopped from the outside. It carries on to
+//!   the end, nobody looks at the result, and the thread goes back to the pool when it's done. So a
+//!   batch full of overrunning searches can still tie the pool up. If that matters, see
+//!   [crate::cancellation] for searches that check in and stop early.
+
+use crate::{spawn_prime_output, PrimeResult};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::timer::Timeout;
+
+/// What to do about a search that runs over its time limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeoutAction {
+    /// Report the overrun as an [SlaViolation].
+    ReturnError,
+    /// Return a [PrimeResult] with a `value` of 0 to show that there's no answer, and an `elapsed`
+    /// of the time limit.
+    ReturnPartial,
+    /// Act as if the search was never asked for, and return nothing.
+    Skip,
+}
+
+/// The service level each search is held to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TaskSla {
+    pub per_task_timeout: Duration,
+    pub on_timeout: TimeoutAction,
+}
+
+/// A search didn't finish in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlaViolation {
+    pub id: u64,
+    pub n: u64,
+    pub timeout: Duration,
+}
+
+impl fmt::Display for SlaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} (the {}th prime) took longer than {:.3}s", self.id, self.n, self.timeout.as_secs_f64())
+    }
+}
+
+impl Error for SlaViolation {}
+
+/// Run [crate::prime_output] for the `n`th prime, but only wait `sla.per_task_timeout` for it.
+///
+/// Returns `Ok(Some(result))` if the search finished in time. Otherwise, the result depends on
+/// `sla.on_timeout`. The time limit includes any time spent waiting for a blocking thread.
+pub async fn prime_output_sla(id: u64, n: u64, sla: TaskSla) -> Result<Option<PrimeResult>, SlaViolation> {
+    let submitted = Instant::now();
+    match Timeout::new(spawn_prime_output(id, n), sla.per_task_timeout).await {
+        Ok(result) => Ok(Some(result)),
+        Err(_) => match sla.on_timeout {
+            TimeoutAction::ReturnError => Err(SlaViolation { id, n, timeout: sla.per_task_timeout }),
+            TimeoutAction::ReturnPartial => {
+                // We don't know when the search got a blocking thread, so count from when it asked
+                let elapsed = sla.per_task_timeout;
+                Ok(Some(PrimeResult { id, n, value: 0, started_at: submitted, elapsed, peak_memory: 0 }))
+            }
+            TimeoutAction::Skip => Ok(None),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn_with_handle;
+    use crate::testing::test_runtime_single_thread;
+    use futures::future::poll_fn;
+    use std::thread;
+    use tokio_executor::threadpool::blocking;
+
+    const TIMEOUT: Duration = Duration::from_millis(20);
+
+    /// Run a search for the 10th prime with a [TIMEOUT] limit, while the only blocking thread is
+    /// busy for much longer than that.
+    fn search_behind_a_busy_thread(on_timeout: TimeoutAction) -> Result<Option<PrimeResult>, SlaViolation> {
+        test_runtime_single_thread().block_on(async {
+            let busy = spawn_with_handle(async {
+                poll_fn(|_| blocking(|| thread::sleep(Duration::from_millis(200)))).await.expect("Couldn't block")
+            });
+            let sla = TaskSla { per_task_timeout: TIMEOUT, on_timeout };
+            let result = prime_output_sla(1, 10, sla).await;
+            busy.await;
+            result
+        })
+    }
+
+    #[test]
+    fn searches_that_finish_in_time_return_their_result() {
+        let sla = TaskSla { per_task_timeout: Duration::from_secs(10), on_timeout: TimeoutAction::ReturnError };
+        let result = crate::prime_test! { prime_output_sla(1, 10, sla).await };
+        assert_eq!(result.unwrap().unwrap().value, 29);
+    }
+
+    #[test]
+    fn overruns_can_be_errors() {
+        let violation = SlaViolation { id: 1, n: 10, timeout: TIMEOUT };
+        assert_eq!(search_behind_a_busy_thread(TimeoutAction::ReturnError), Err(violation));
+    }
+
+    #[test]
+    fn overruns_can_be_partial_results() {
+        let result = search_behind_a_busy_thread(TimeoutAction::ReturnPartial).unwrap().unwrap();
+        assert_eq!((result.id, result.n, result.value, result.elapsed), (1, 10, 0, TIMEOUT));
+    }
+
+    #[test]
+    fn overruns_can_be_skipped() {
+        assert_eq!(search_behind_a_busy_thread(TimeoutAction::Skip), Ok(None));
+    }
+}
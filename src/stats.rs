@@ -0,0 +1,61 @@
+//! Summing up how well a run used the blocking threads it had.
+
+use crate::PrimeResult;
+use std::time::Duration;
+
+/// Roughly what fraction of the time `n_threads` blocking threads spent idle, for a run that took
+/// `wall_time` from start to finish and produced `results`.
+///
+/// The run had `n_threads * wall_time` thread-seconds to give, and the searches used the sum of
+/// their `elapsed` times. Whatever's left was idle: 0 means every thread was busy the whole time,
+/// and 1 means none of them did anything. Returns 0 if there was no time to give at all.
+///
+/// Take the run in the doc comment on `main` in `main.rs`: 20 searches took 3160 thread-seconds
+/// between them, and the last one finished 638 seconds in. 5 threads over 638 seconds is 3190
+/// thread-seconds, so the threads were idle about 1% of the time. That's the point of starting
+/// with the hardest searches: no thread is left waiting at the end for one long search to finish.
+///
+/// This is only an estimate: the `elapsed` times are measured on the blocking threads themselves,
+/// so any time spent handing the work over counts as idle.
+pub fn estimated_blocking_thread_idle_fraction(results: &[PrimeResult], n_threads: usize, wall_time: Duration) -> f64 {
+    let available = n_threads as f64 * wall_time.as_secs_f64();
+    if available <= 0.0 {
+        return 0.0;
+    }
+    let used = results.iter().map(|r| r.elapsed.as_secs_f64()).sum::<f64>();
+    (1.0 - used / available).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// A result for each of `seconds`, that took that long.
+    fn results(seconds: &[u64]) -> Vec<PrimeResult> {
+        let started_at = Instant::now();
+        let result = |(id, &s)| {
+            PrimeResult { id, n: 1, value: 2, started_at, elapsed: Duration::from_secs(s), peak_memory: 0 }
+        };
+        (0..).zip(seconds).map(result).collect()
+    }
+
+    #[test]
+    fn idle_fraction_is_whatever_the_searches_left() {
+        // 638 thread-seconds out of 5 * 300 = 1500
+        let idle = estimated_blocking_thread_idle_fraction(&results(&[300, 200, 100, 38]), 5, Duration::from_secs(300));
+        assert!((idle - 0.5747).abs() < 1e-4, "idle fraction was {}", idle);
+        // The run in main.rs: 3160 thread-seconds out of 5 * 638 = 3190
+        let idle = estimated_blocking_thread_idle_fraction(&results(&[3160]), 5, Duration::from_secs(638));
+        assert!((idle - 0.0094).abs() < 1e-4, "idle fraction was {}", idle);
+    }
+
+    #[test]
+    fn idle_fraction_stays_between_0_and_1() {
+        let busy = results(&[10, 10]);
+        assert_eq!(estimated_blocking_thread_idle_fraction(&busy, 1, Duration::from_secs(10)), 0.0);
+        assert_eq!(estimated_blocking_thread_idle_fraction(&[], 4, Duration::from_secs(10)), 1.0);
+        assert_eq!(estimated_blocking_thread_idle_fraction(&busy, 0, Duration::from_secs(10)), 0.0);
+        assert_eq!(estimated_blocking_thread_idle_fraction(&busy, 4, Duration::ZERO), 0.0);
+    }
+}
@@ -0,0 +1,285 @@
+//! Streams of prime search results.
+
+use crate::primality::PrimeIterator;
+use crate::sieve::segmented_sieve;
+use crate::{spawn_prime_output, spawn_with_handle, PrimeResult};
+use futures::channel::mpsc;
+use futures::future::{self, poll_fn, RemoteHandle};
+use futures::sink::SinkExt;
+use futures::stream::{FuturesUnordered, Stream, StreamExt};
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio_executor::threadpool::blocking;
+
+/// Puts a stream of results back into id order.
+///
+/// Results from `inner` usually turn up in whatever order they finish in. `SortedResultStream`
+/// holds on to any result that arrives early, and only yields a result once every result with a
+/// smaller id has been yielded. If `inner` ends while a gap remains (say, an id was never
+/// submitted), whatever is left over is yielded in id order.
+pub struct SortedResultStream<S> {
+    inner: S,
+    buffer: BTreeMap<u64, PrimeResult>,
+    next_id: u64,
+}
+
+impl<S> SortedResultStream<S>
+where
+    S: Stream<Item = PrimeResult> + Unpin,
+{
+    /// Sort the results of `inner`, starting with the result with id `first_id`.
+    pub fn new(inner: S, first_id: u64) -> Self {
+        SortedResultStream { inner, buffer: BTreeMap::new(), next_id: first_id }
+    }
+
+    /// Pop the buffered result with the smallest id if it's the one we're waiting for, or if we're
+    /// not going to get it anyway because `inner` has finished.
+    fn pop_buffered(&mut self, inner_done: bool) -> Option<PrimeResult> {
+        let &id = self.buffer.keys().next()?;
+        if id != self.next_id && !inner_done {
+            return None;
+        }
+        self.next_id = id + 1;
+        self.buffer.remove(&id)
+    }
+}
+
+impl<S> Stream for SortedResultStream<S>
+where
+    S: Stream<Item = PrimeResult> + Unpin,
+{
+    type Item = PrimeResult;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<PrimeResult>> {
+        loop {
+            if let Some(result) = self.pop_buffered(false) {
+                return Poll::Ready(Some(result));
+            }
+            match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(result)) => {
+                    self.buffer.insert(result.id, result);
+                }
+                Poll::Ready(None) => return Poll::Ready(self.pop_buffered(true)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Spawn all the `(id, n)` searches, and stream the results back in id order.
+pub fn sorted_prime_stream(tasks: Vec<(u64, u64)>) -> SortedResultStream<impl Stream<Item = PrimeResult>> {
+    let first_id = tasks.iter().map(|&(id, _)| id).min().unwrap_or(0);
+    let results = tasks.into_iter().map(|(id, n)| spawn_prime_output(id, n)).collect::<FuturesUnordered<_>>();
+    SortedResultStream::new(results, first_id)
+}
+
+/// Run the `(id, n)` searches twice: once collecting the results in the order they finish, and once
+/// through a [SortedResultStream], then print the two orders side by side.
+///
+/// Returns the `(completion order, submission order)` results.
+pub async fn compare_orderings(tasks: Vec<(u64, u64)>) -> (Vec<PrimeResult>, Vec<PrimeResult>) {
+    let unordered = tasks.iter().map(|&(id, n)| spawn_prime_output(id, n)).collect::<FuturesUnordered<_>>();
+    let completion_order = unordered.collect::<Vec<_>>().await;
+    let submission_order = sorted_prime_stream(tasks).collect::<Vec<_>>().await;
+    print!("{}", format_ordering_table(&completion_order, &submission_order));
+    (completion_order, submission_order)
+}
+
+/// Lay out two lists of results as a two column table.
+pub fn format_ordering_table(completion_order: &[PrimeResult], submission_order: &[PrimeResult]) -> String {
+    let cell = |r: Option<&PrimeResult>| match r {
+        Some(r) => format!("#{:2}, {:7}th = {:10}", r.id, r.n, r.value),
+        None => String::new(),
+    };
+    let mut table = format!("{:<28} | {}\n{:-<28}-+-{:-<28}\n", "Completion Order", "Submission Order", "", "");
+    for i in 0..completion_order.len().max(submission_order.len()) {
+        let line = format!("{:<28} | {}", cell(completion_order.get(i)), cell(submission_order.get(i)));
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// How many primes [AsyncPrimeIterator]'s background task can get ahead of whoever is reading them.
+pub const PRIME_CHANNEL_CAPACITY: usize = 64;
+
+/// The primes from some starting point on, found by a background task and streamed back over a
+/// channel.
+///
+/// The background task finds each prime on a blocking thread, and waits whenever the channel is
+/// full, so it never gets more than [PRIME_CHANNEL_CAPACITY] primes ahead. Dropping the stream
+/// cancels the background task. If `blocking` fails, the stream just ends.
+pub struct AsyncPrimeIterator {
+    rx: mpsc::Receiver<u64>,
+    _handle: RemoteHandle<()>,
+}
+
+impl AsyncPrimeIterator {
+    /// Stream the primes that are at least `n`. This spawns the background task, so it has to be
+    /// called from inside the runtime.
+    pub fn starting_from(n: u64) -> Self {
+        let (mut tx, rx) = mpsc::channel(PRIME_CHANNEL_CAPACITY);
+        let mut primes = PrimeIterator::starting_from(n);
+        let _handle = spawn_with_handle(async move {
+            while let Ok(Some(p)) = poll_fn(|_| blocking(|| primes.next())).await {
+                if tx.send(p).await.is_err() {
+                    break;
+                }
+            }
+        });
+        AsyncPrimeIterator { rx, _handle }
+    }
+
+    /// Stop the stream after the last prime that is no more than `high`.
+    pub fn take_while_le(self, high: u64) -> impl Stream<Item = u64> {
+        self.take_while(move |&p| future::ready(p <= high))
+    }
+}
+
+impl Stream for AsyncPrimeIterator {
+    type Item = u64;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<u64>> {
+        Pin::new(&mut self.rx).poll_next(cx)
+    }
+}
+
+/// How many chunks the background tasks behind [prime_stream_chunked] and
+/// [prime_stream_chunked_sieve] can get ahead of whoever is reading them.
+pub const CHUNK_CHANNEL_CAPACITY: usize = 4;
+
+/// Spawn a task that keeps calling `next_chunk` on a blocking thread and sending the chunks it
+/// returns, until it returns `None` or the receiver is dropped.
+pub(crate) fn spawn_chunk_producer<F>(mut next_chunk: F) -> mpsc::Receiver<Vec<u64>>
+where
+    F: FnMut() -> Option<Vec<u64>> + Send + 'static,
+{
+    let (mut tx, rx) = mpsc::channel(CHUNK_CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        while let Ok(Some(chunk)) = poll_fn(|_| blocking(&mut next_chunk)).await {
+            if tx.send(chunk).await.is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// The same primes as [AsyncPrimeIterator::starting_from], but `chunk_size` at a time.
+///
+/// Every trip through the channel, and every hop onto a blocking thread, costs the same whether it
+/// carries one prime or a thousand, so batching them up spreads that cost out. The background task
+/// stops once the stream is dropped. Like [AsyncPrimeIterator], this has to be called from inside
+/// the runtime. Panics if `chunk_size` is 0.
+pub fn prime_stream_chunked(start: u64, chunk_size: usize) -> impl Stream<Item = Vec<u64>> {
+    // Every chunk would be empty, which looks just like having run out of primes
+    assert!(chunk_size > 0, "Chunks have to hold at least one prime");
+    let mut primes = PrimeIterator::starting_from(start);
+    spawn_chunk_producer(move || {
+        let chunk = primes.by_ref().take(chunk_size).collect::<Vec<_>>();
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(chunk)
+        }
+    })
+}
+
+/// The primes at least `start`, with each chunk holding the primes in the next `segment_size`
+/// numbers, found with [segmented_sieve]. The sieve is much quicker than testing each number in
+/// turn, but it means the chunks aren't all the same size. Empty segments are skipped.
+pub fn prime_stream_chunked_sieve(start: u64, segment_size: u64) -> impl Stream<Item = Vec<u64>> {
+    let mut low = Some(start);
+    spawn_chunk_producer(move || {
+        while let Some(lo) = low {
+            let hi = lo.saturating_add(segment_size.max(1));
+            low = if hi == u64::MAX { None } else { Some(hi) };
+            let chunk = segmented_sieve(lo, hi);
+            if !chunk.is_empty() {
+                return Some(chunk);
+            }
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use std::time::{Duration, Instant};
+
+    fn result(id: u64) -> PrimeResult {
+        let value = crate::sieve::first_primes(id as usize + 1)[id as usize];
+        PrimeResult { id, n: id + 1, value, started_at: Instant::now(), elapsed: Duration::ZERO, peak_memory: 0 }
+    }
+
+    #[test]
+    fn sorted_stream_yields_in_id_order() {
+        let scrambled = stream::iter([3, 1, 0, 4, 2].iter().map(|&id| result(id)));
+        let sorted = futures::executor::block_on(SortedResultStream::new(scrambled, 0).collect::<Vec<_>>());
+        assert_eq!(sorted.iter().map(|r| r.id).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn sorted_stream_yields_whatever_is_left_after_a_gap() {
+        let with_gap = stream::iter([4, 1, 3].iter().map(|&id| result(id)));
+        let sorted = futures::executor::block_on(SortedResultStream::new(with_gap, 1).collect::<Vec<_>>());
+        assert_eq!(sorted.iter().map(|r| r.id).collect::<Vec<_>>(), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn sorted_prime_stream_puts_spawned_searches_in_order() {
+        crate::prime_test! {
+            // The hardest search first, so it's the last to finish
+            let tasks = vec![(0, 200_000), (1, 100), (2, 10), (3, 1000)];
+            let results = sorted_prime_stream(tasks).collect::<Vec<_>>().await;
+            let values = results.iter().map(|r| (r.id, r.value)).collect::<Vec<_>>();
+            assert_eq!(values, vec![(0, 2_750_159), (1, 541), (2, 29), (3, 7919)]);
+        }
+    }
+
+    #[test]
+    fn orderings_hold_the_same_results() {
+        crate::prime_test! {
+            // The hardest search first, so it's the last to finish
+            let tasks = (0..10).map(|id| (id, if id == 0 { 200_000 } else { id * 10 })).collect::<Vec<_>>();
+            let (completion_order, submission_order) = compare_orderings(tasks).await;
+            let ids = |results: &[PrimeResult]| results.iter().map(|r| r.id).collect::<Vec<_>>();
+            assert_eq!(ids(&submission_order), (0..10).collect::<Vec<_>>());
+            assert_ne!(ids(&completion_order), ids(&submission_order));
+            let mut completion_order = completion_order;
+            completion_order.sort_by_key(|r| r.id);
+            let found = |results: &[PrimeResult]| results.iter().map(|r| (r.id, r.n, r.value)).collect::<Vec<_>>();
+            assert_eq!(found(&completion_order), found(&submission_order));
+        }
+    }
+
+    #[test]
+    fn async_prime_iterator_streams_primes() {
+        crate::prime_test! {
+            let primes = AsyncPrimeIterator::starting_from(10).take(10).collect::<Vec<_>>().await;
+            assert_eq!(primes, vec![11, 13, 17, 19, 23, 29, 31, 37, 41, 43]);
+            let primes = AsyncPrimeIterator::starting_from(90).take_while_le(110).collect::<Vec<_>>().await;
+            assert_eq!(primes, vec![97, 101, 103, 107, 109]);
+        }
+    }
+
+    #[test]
+    fn chunks_split_the_primes_without_gaps() {
+        crate::prime_test! {
+            let chunks = prime_stream_chunked(2, 100).take(3).collect::<Vec<_>>().await;
+            assert!(chunks.iter().all(|chunk| chunk.len() == 100));
+            assert_eq!(chunks.concat(), crate::sieve::first_primes(300));
+            let chunks = prime_stream_chunked_sieve(1000, 100).take(3).collect::<Vec<_>>().await;
+            assert_eq!(chunks.concat(), segmented_sieve(1000, 1300));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one prime")]
+    fn empty_chunks_are_rejected() {
+        let _ = prime_stream_chunked(2, 0);
+    }
+}
@@ -0,0 +1,145 @@
+//! Checking that the blocking pool keeps up under load.
+//!
+//! [stress_test_blocking_pool] floods a fresh runtime with tasks that each hold a blocking thread
+//! for a fixed time, and measures how long they queued and how many got through per second. The
+//! tasks sleep rather than compute, so the numbers measure the runtime's scheduling rather than
+//! the CPU. Save a [StressReport] from a good build, and [check_regression] will tell you if a
+//! later one has slowed down.
+
+use crate::spawn_with_handle;
+use futures::channel::oneshot;
+use futures::future::{join_all, poll_fn};
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Builder;
+use tokio_executor::threadpool::blocking;
+
+/// How hard [stress_test_blocking_pool] pushes, and on what size of runtime.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StressConfig {
+    pub n_tasks: usize,
+    /// How long each task holds on to its blocking thread
+    pub task_duration_ms: u64,
+    pub blocking_threads: usize,
+    pub core_threads: usize,
+}
+
+impl Default for StressConfig {
+    /// 200 tasks of 10ms each, on a runtime the shape of the demo's: 5 blocking threads and 1 core
+    /// thread. That's 400ms if nothing gets in the way.
+    fn default() -> Self {
+        StressConfig { n_tasks: 200, task_duration_ms: 10, blocking_threads: 5, core_threads: 1 }
+    }
+}
+
+/// What [stress_test_blocking_pool] measured.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StressReport {
+    pub tasks_completed: usize,
+    /// From spawning the first task to the last one finishing
+    pub total_wall_ms: u64,
+    /// The median time a task waited for a blocking thread
+    pub p50_wait_ms: u64,
+    /// The time 99% of tasks waited no longer than for a blocking thread
+    pub p99_wait_ms: u64,
+    /// Tasks completed per second of wall time
+    pub throughput_rps: f64,
+}
+
+impl fmt::Display for StressReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} tasks in {}ms ({:.1}/s), waiting {}ms at p50 and {}ms at p99",
+            self.tasks_completed, self.total_wall_ms, self.throughput_rps, self.p50_wait_ms, self.p99_wait_ms
+        )
+    }
+}
+
+/// Run `config.n_tasks` tasks, each sleeping for `config.task_duration_ms` on a blocking thread,
+/// all spawned at once, and report how it went.
+///
+/// The tasks run on a runtime built to `config`, on a thread of its own, so they don't compete
+/// with whatever runtime this is called from (and the calling runtime doesn't need any blocking
+/// threads free). This resolves once that runtime has finished and shut down.
+pub async fn stress_test_blocking_pool(config: StressConfig) -> StressReport {
+    let (tx, rx) = oneshot::channel();
+    thread::spawn(move || tx.send(run_stress_test(config)).ok());
+    rx.await.expect("The stress test panicked")
+}
+
+fn run_stress_test(config: StressConfig) -> StressReport {
+    let rt = Builder::new()
+        .blocking_threads(config.blocking_threads.max(1))
+        .core_threads(config.core_threads.max(1))
+        .build()
+        .expect("Couldn't build the runtime");
+    let duration = Duration::from_millis(config.task_duration_ms);
+    let start = Instant::now();
+    let mut waits = rt.block_on(async move {
+        let tasks = (0..config.n_tasks).map(|_| {
+            let submitted = Instant::now();
+            spawn_with_handle(async move {
+                poll_fn(move |_| {
+                    blocking(|| {
+                        let waited = submitted.elapsed();
+                        thread::sleep(duration);
+                        waited
+                    })
+                }).await.expect("Couldn't block")
+            })
+        });
+        join_all(tasks.collect::<Vec<_>>()).await
+    });
+    let wall = start.elapsed();
+    rt.shutdown_on_idle();
+    waits.sort();
+    let percentile = |p: f64| match waits.len() {
+        0 => 0,
+        len => waits[((p * len as f64).ceil() as usize).clamp(1, len) - 1].as_millis() as u64,
+    };
+    StressReport {
+        tasks_completed: waits.len(),
+        total_wall_ms: wall.as_millis() as u64,
+        p50_wait_ms: percentile(0.5),
+        p99_wait_ms: percentile(0.99),
+        throughput_rps: waits.len() as f64 / wall.as_secs_f64(),
+    }
+}
+
+/// Whether `current` has kept up with `baseline`: `true` unless its throughput is more than
+/// `tolerance` percent below the baseline's. So with a `tolerance` of 10, a baseline of 500
+/// tasks/s passes anything from 450 tasks/s up.
+pub fn check_regression(baseline: &StressReport, current: &StressReport, tolerance: f64) -> bool {
+    current.throughput_rps >= baseline.throughput_rps * (1.0 - tolerance / 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(throughput_rps: f64) -> StressReport {
+        StressReport { tasks_completed: 100, total_wall_ms: 1000, p50_wait_ms: 0, p99_wait_ms: 0, throughput_rps }
+    }
+
+    #[test]
+    fn every_task_finishes() {
+        let config = StressConfig { n_tasks: 20, task_duration_ms: 10, blocking_threads: 4, core_threads: 1 };
+        let report = crate::prime_test! { stress_test_blocking_pool(config).await };
+        assert_eq!(report.tasks_completed, 20);
+        // 20 tasks of 10ms, 4 at a time, can't take less than 50ms
+        assert!(report.total_wall_ms >= 50, "{}", report);
+        assert!(report.p50_wait_ms <= report.p99_wait_ms);
+        assert!(report.throughput_rps > 0.0);
+    }
+
+    #[test]
+    fn regressions_are_drops_past_the_tolerance() {
+        let baseline = report(500.0);
+        assert!(check_regression(&baseline, &report(450.0), 10.0));
+        assert!(check_regression(&baseline, &report(600.0), 10.0));
+        assert!(!check_regression(&baseline, &report(449.0), 10.0));
+        assert!(!check_regression(&baseline, &report(499.0), 0.0));
+    }
+}
@@ -0,0 +1,66 @@
+//! Numbers whose primality we know for certain, for checking the primality tests against.
+//!
+//! The list leans on the cases that trip primality tests up: the edges at 0, 1 and 2, numbers
+//! that fool Fermat tests (Carmichael numbers) or Miller-Rabin for a particular base (strong
+//! pseudoprimes), and values big enough to overflow careless modular arithmetic.
+
+use crate::sieve::sieve_primes;
+use std::collections::BTreeMap;
+
+/// Carmichael numbers: composites that pass the Fermat test for every base coprime to them.
+pub static CARMICHAEL_NUMBERS: [u64; 7] = [561, 1105, 1729, 2465, 2821, 6601, 8911];
+
+/// Composites that pass Miller-Rabin for some of the smallest bases. The last two pass for every
+/// prime base up to 7 and 23 respectively.
+pub static STRONG_PSEUDOPRIMES: [u64; 13] = [
+    // Base 2
+    2047,
+    3277,
+    4033,
+    4681,
+    8321,
+    // Base 3
+    121,
+    703,
+    1891,
+    // Base 5
+    781,
+    1541,
+    // Bases 2 and 3
+    1_373_653,
+    // Bases 2, 3, 5 and 7
+    3_215_031_751,
+    // Every prime base up to 23
+    3_825_123_056_546_413_051,
+];
+
+/// Every `(n, is n prime)` pair we have, sorted by `n`.
+///
+/// Some of the primes are big (up to the largest prime below `2^64`), so only run the slow trial
+/// division tests over the ones they can finish in a reasonable time.
+pub fn generate_prime_test_vectors() -> Vec<(u64, bool)> {
+    let mut vectors = BTreeMap::new();
+    let primes = sieve_primes(1000);
+    for &p in &primes {
+        vectors.insert(p, true);
+    }
+    vectors.insert(0, false);
+    vectors.insert(1, false);
+    for n in (4..=100).step_by(2) {
+        vectors.insert(n, false);
+    }
+    for n in (9..=99).step_by(2).filter(|n| primes.binary_search(n).is_err()) {
+        vectors.insert(n, false);
+    }
+    for &n in CARMICHAEL_NUMBERS.iter().chain(&STRONG_PSEUDOPRIMES) {
+        vectors.insert(n, false);
+    }
+    let m31 = (1 << 31) - 1;
+    let m61 = (1 << 61) - 1;
+    vectors.insert(m31, true);
+    vectors.insert(m61, true);
+    vectors.insert(18_446_744_073_709_551_557, true);
+    vectors.insert(m31 * m31, false);
+    vectors.insert(u64::MAX, false);
+    vectors.into_iter().collect()
+}
@@ -0,0 +1,87 @@
+//! Helpers for building runtimes in tests.
+//!
+//! There are two runtime flavours in Tokio:
+//!
+//! * The thread pool runtime (`tokio::runtime::Runtime`) has a set of "core" threads that poll
+//!   futures, and a separate pool of "blocking" threads that `blocking` hands work off to. This is
+//!   what `#[tokio::main]` gives you by default (`multi_thread`).
+//! * The current thread runtime (`tokio::runtime::current_thread::Runtime`, or `single_thread` in
+//!   the attribute macros) polls everything on the thread that called `block_on`. It has no
+//!   blocking pool at all, so `blocking` always returns a `BlockingError` there.
+//!
+//! Every prime search in this crate leans on `blocking`, so tests need a thread pool runtime. But
+//! `#[tokio::test]` always builds a current thread runtime, and neither attribute lets you pick how
+//! many blocking threads you get (which matters when you're testing scheduling behaviour), so build
+//! the runtime with these helpers instead.
+//!
+//! Remember that `block_on` runs the future on the *calling* thread, not on a pool worker, so
+//! anything that calls `blocking` still has to be `spawn`ed onto the runtime.
+
+use tokio::runtime::{Builder, Runtime};
+
+/// Build a thread pool runtime with `blocking_threads` blocking threads and a single core thread,
+/// the same shape as the runtime `main` uses.
+pub fn test_runtime(blocking_threads: usize) -> Runtime {
+    Builder::new()
+        .blocking_threads(blocking_threads)
+        .core_threads(1)
+        .build()
+        .expect("Could not create test runtime")
+}
+
+/// Build the smallest thread pool runtime that can still run `blocking` code: one core thread and
+/// one blocking thread. Blocking tasks run strictly one after the other, which makes it handy for
+/// tests that care about ordering.
+pub fn test_runtime_single_thread() -> Runtime {
+    test_runtime(1)
+}
+
+/// Run the given block as an `async` block on a fresh `test_runtime(4)`.
+///
+/// ```ignore
+/// #[test]
+/// fn finds_the_tenth_prime() {
+///     prime_test! {
+///         assert_eq!(find_nth_prime(10), 29);
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! prime_test {
+    ($($body:tt)*) => {
+        $crate::testing::test_runtime(4).block_on(async { $($body)* })
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{find_nth_prime, spawn_prime_output, spawn_with_handle, try_prime_output};
+
+    #[test]
+    fn prime_test_runs_its_block() {
+        crate::prime_test! {
+            assert_eq!(find_nth_prime(10), 29);
+        }
+    }
+
+    #[test]
+    fn spawned_searches_can_block() {
+        crate::prime_test! {
+            assert_eq!(spawn_prime_output(0, 10).await.value, 29);
+        }
+    }
+
+    #[test]
+    fn single_thread_runtime_can_block() {
+        let rt = test_runtime_single_thread();
+        let result = rt.block_on(async { spawn_with_handle(try_prime_output(0, 100)).await });
+        assert_eq!(result.expect("Couldn't block").value, 541);
+    }
+
+    #[test]
+    fn block_on_alone_cannot_block() {
+        let rt = test_runtime(1);
+        assert!(rt.block_on(try_prime_output(0, 100)).is_err());
+    }
+}
@@ -0,0 +1,36 @@
+//! Keeping track of which request a search belongs to, when the search is spawned.
+//!
+//! In `tracing`, the current span is a per-thread thing: it's whichever span was most recently
+//! entered on *this* thread, and not yet exited. `blocking` itself doesn't lose it: the closure
+//! runs inline, in the same poll, on the thread that's polling the task, so a search awaited
+//! inside the caller's span logs inside that span too (which is what [crate::instrumented]
+//! relies on). The trouble starts when the search is `tokio::spawn`ed without `.instrument(..)`.
+//! The spawned task gets polled by whichever pool thread picks it up, and that thread never
+//! entered the caller's span. So inside the blocking closure, `tracing::Span::current()` is no
+//! span at all, or worse, some unrelated span the thread happened to be in, and any events the
+//! search logs lose track of the request they were for.
+//!
+//! Instrumenting the future before spawning it fixes that. So does what
+//! [prime_output_with_tracing_context] does, which works however the search ends up being run:
+//! capture the span on the caller's side, move it into the closure, and enter it there.
+
+use crate::primality::find_nth_prime_with_sieve_fallback;
+use crate::{time_search, PrimeResult};
+use futures::future::poll_fn;
+use tokio_executor::threadpool::blocking;
+use tracing::{info, Span};
+
+/// The same search as [crate::prime_output], but the blocking closure runs inside `cx`, so the
+/// events it logs, and the time it spends, are recorded against the caller's span even if this
+/// is spawned. Pass `Span::current()` to use whatever span the caller is in.
+pub async fn prime_output_with_tracing_context(id: u64, n: u64, cx: Span) -> PrimeResult {
+    poll_fn(|_| {
+        blocking(|| {
+            let _entered = cx.enter();
+            info!(id = id, n = n, "searching for prime");
+            let result = time_search(id, n, find_nth_prime_with_sieve_fallback);
+            info!(id = id, value = result.value, elapsed_ms = result.elapsed.as_millis() as u64, "found prime");
+            result
+        })
+    }).await.expect("Couldn't block")
+}
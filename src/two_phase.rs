@@ -0,0 +1,182 @@
+//! Collecting results in two phases: no result is looked at until every search has finished.
+//!
+//! In phase 1, each search runs on its own and puts its result in its slot of a shared
+//! `Vec<Option<PrimeResult>>`. Then it waits at a [Barrier] sized to the number of searches. Nobody
+//! gets past the barrier until everyone has arrived, so once it opens, phase 2 knows every slot
+//! has been filled, and can read them all without checking.
+//!
+//! Tokio doesn't have a barrier yet, so [Barrier] is a small one of our own, built the same way as
+//! [crate::cancellation::CancellationToken]: some state and a list of wakers behind a mutex.
+
+use crate::{prime_output, spawn_with_handle, PrimeResult};
+use futures::future::join_all;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Makes a fixed number of tasks wait until they have all reached the same point.
+///
+/// A barrier can be reused: once it opens, the next `n` calls to [Barrier::wait] wait for each
+/// other all over again.
+pub struct Barrier {
+    n: usize,
+    state: Mutex<BarrierState>,
+}
+
+struct BarrierState {
+    arrived: usize,
+    /// Goes up by one every time the barrier opens, so a waiter can tell its barrier has opened
+    /// even if more tasks have started arriving for the next round.
+    generation: u64,
+    waiters: Vec<Waker>,
+}
+
+/// What [Barrier::wait] resolves to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BarrierWaitResult {
+    leader: bool,
+}
+
+impl BarrierWaitResult {
+    /// Whether this was the last task to arrive. Exactly one of the tasks waiting on each round
+    /// is the leader, which makes it a handy way to pick the task that does the phase 2 work.
+    pub fn is_leader(&self) -> bool {
+        self.leader
+    }
+}
+
+impl Barrier {
+    /// A barrier that opens once `n` tasks are waiting on it. A barrier for no tasks opens for
+    /// anyone, just like a barrier for one.
+    pub fn new(n: usize) -> Self {
+        let state = BarrierState { arrived: 0, generation: 0, waiters: Vec::new() };
+        Barrier { n: n.max(1), state: Mutex::new(state) }
+    }
+
+    /// Wait for the rest of the `n` tasks to arrive.
+    pub async fn wait(&self) -> BarrierWaitResult {
+        let generation = {
+            let mut state = self.state.lock().unwrap();
+            state.arrived += 1;
+            if state.arrived == self.n {
+                state.arrived = 0;
+                state.generation += 1;
+                for waker in state.waiters.drain(..) {
+                    waker.wake();
+                }
+                return BarrierWaitResult { leader: true };
+            }
+            state.generation
+        };
+        BarrierOpened { barrier: self, generation }.await;
+        BarrierWaitResult { leader: false }
+    }
+}
+
+/// Resolves once the barrier has moved on from `generation`.
+struct BarrierOpened<'a> {
+    barrier: &'a Barrier,
+    generation: u64,
+}
+
+impl Future for BarrierOpened<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.barrier.state.lock().unwrap();
+        if state.generation != self.generation {
+            return Poll::Ready(());
+        }
+        if !state.waiters.iter().any(|w| w.will_wake(cx.waker())) {
+            state.waiters.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// Run the searches in `tasks`, given as `(id, n)` pairs, and return their results in the same
+/// order, in two phases.
+///
+/// Each search fills its own slot, then waits at a barrier for the rest. The last one to arrive
+/// is the leader, and it's the only one that touches the results after that: it takes them all
+/// out of their slots and hands them back. Like [crate::prime_output], this has to run inside the
+/// thread pool runtime.
+pub async fn two_phase_prime_collect(tasks: Vec<(u64, u64)>) -> Vec<PrimeResult> {
+    if tasks.is_empty() {
+        return Vec::new();
+    }
+    let barrier = Arc::new(Barrier::new(tasks.len()));
+    let slots = Arc::new(Mutex::new(vec![None; tasks.len()]));
+    let searches = tasks.into_iter().enumerate().map(|(i, (id, n))| {
+        let barrier = barrier.clone();
+        let slots = slots.clone();
+        spawn_with_handle(async move {
+            // Phase 1: compute, and fill our slot
+            let result = prime_output(id, n).await;
+            slots.lock().unwrap()[i] = Some(result);
+            if !barrier.wait().await.is_leader() {
+                return None;
+            }
+            // Phase 2: everyone has arrived, so every slot is full
+            let results = slots.lock().unwrap().drain(..).map(|r| r.expect("A slot was empty")).collect();
+            Some(results)
+        })
+    });
+    let mut collected = join_all(searches.collect::<Vec<_>>()).await;
+    collected.iter_mut().find_map(Option::take).expect("No search was the leader")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn nobody_passes_the_barrier_until_everyone_arrives() {
+        crate::prime_test! {
+            let barrier = Arc::new(Barrier::new(5));
+            let log = Arc::new(Mutex::new(Vec::new()));
+            let tasks = [30, 0, 20, 50, 10].iter().enumerate().map(|(i, &wait)| {
+                let (barrier, log) = (barrier.clone(), log.clone());
+                spawn_with_handle(async move {
+                    tokio::timer::delay(tokio::clock::now() + Duration::from_millis(wait)).await;
+                    log.lock().unwrap().push(("arrived", i));
+                    let leader = barrier.wait().await.is_leader();
+                    log.lock().unwrap().push(("passed", i));
+                    leader
+                })
+            });
+            let leaders = join_all(tasks.collect::<Vec<_>>()).await;
+            // The one that waited longest arrived last
+            assert_eq!(leaders, vec![false, false, false, true, false]);
+            let log = log.lock().unwrap();
+            assert!(log[..5].iter().all(|&(event, _)| event == "arrived"), "{:?}", log);
+            assert!(log[5..].iter().all(|&(event, _)| event == "passed"), "{:?}", log);
+        }
+    }
+
+    #[test]
+    fn barriers_can_be_reused() {
+        crate::prime_test! {
+            let barrier = Arc::new(Barrier::new(2));
+            for _ in 0..3 {
+                let other = barrier.clone();
+                let other = spawn_with_handle(async move { other.wait().await.is_leader() });
+                let leader = barrier.wait().await.is_leader();
+                assert_ne!(leader, other.await);
+            }
+        }
+    }
+
+    #[test]
+    fn five_searches_come_back_in_order() {
+        crate::prime_test! {
+            let tasks = vec![(0, 20_000), (1, 10), (2, 5000), (3, 1), (4, 100)];
+            let results = two_phase_prime_collect(tasks).await;
+            let values: Vec<_> = results.iter().map(|r| (r.id, r.value)).collect();
+            assert_eq!(values, vec![(0, 224_737), (1, 29), (2, 48_611), (3, 2), (4, 541)]);
+            assert!(two_phase_prime_collect(Vec::new()).await.is_empty());
+        }
+    }
+}
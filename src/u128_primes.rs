@@ -0,0 +1,197 @@
+//! Primes beyond `u64`.
+//!
+//! Everything else in the crate stops at `u64::MAX`. Miller-Rabin works just as well on `u128`s,
+//! but the modular multiplication at its heart gets harder: for `u64`s we can multiply in `u128`
+//! and reduce, but there's no `u256` to do the same for `u128`s. So when the product won't fit, we
+//! multiply the slow way, by doubling and adding, reducing as we go. That takes up to 128 steps
+//! instead of one instruction. Just above `2^64` most of the products still fit, so a test there
+//! only takes about twice as long as one just below `2^64` (partly because it tries 20 bases
+//! rather than 12). By `2^126`, hardly any products fit, and each test takes about 100 times as
+//! long.
+
+use crate::primality::{miller_rabin, WITNESSES};
+use crate::sieve::sieve_primes;
+use std::thread;
+
+/// The bases we test `u128`s against: the first 20 primes.
+///
+/// Unlike [WITNESSES] for `u64`, nobody has found a set of bases that's proven to work for every
+/// `u128`, so with these [is_prime_u128] is a probable-prime test with no proven error bound: a
+/// composite that passes all 20 isn't known, but isn't ruled out either. (The familiar "at most 1/4
+/// per base" bound only holds for bases picked at random, not for a fixed list like this one.)
+pub const WITNESSES_U128: [u128; 20] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71];
+
+/// `a + b (mod m)`, for `a, b < m`, without overflowing.
+fn add_mod(a: u128, b: u128, m: u128) -> u128 {
+    if a >= m - b {
+        a - (m - b)
+    } else {
+        a + b
+    }
+}
+
+/// `a * b (mod m)`, for `a, b < m`.
+fn mul_mod(a: u128, b: u128, m: u128) -> u128 {
+    if let Some(product) = a.checked_mul(b) {
+        return product % m;
+    }
+    let (mut a, mut b, mut result) = (a, b, 0);
+    while b > 0 {
+        if b & 1 == 1 {
+            result = add_mod(result, a, m);
+        }
+        a = add_mod(a, a, m);
+        b >>= 1;
+    }
+    result
+}
+
+fn pow_mod(mut base: u128, mut exp: u128, m: u128) -> u128 {
+    let mut result = 1 % m;
+    base %= m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Whether `n` is prime. Anything that fits in a `u64` gets the deterministic test from
+/// [crate::primality]. Above that, see [WITNESSES_U128].
+pub fn is_prime_u128(n: u128) -> bool {
+    if n <= u64::MAX as u128 {
+        return miller_rabin(n as u64, &WITNESSES);
+    }
+    for &p in &WITNESSES_U128 {
+        if n.is_multiple_of(p) {
+            return false;
+        }
+    }
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    WITNESSES_U128.iter().all(|&a| {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// How many primes `p` there are with `low <= p < high`, testing every odd number in turn.
+pub fn count_primes_range_u128(low: u128, high: u128) -> u128 {
+    let mut count = if low <= 2 && high > 2 { 1 } else { 0 };
+    let mut n = low.max(3) | 1;
+    while n < high {
+        if is_prime_u128(n) {
+            count += 1;
+        }
+        n += 2;
+    }
+    count
+}
+
+/// The primes we cross off with before testing what's left in [count_primes_sieve_parallel_u128].
+const PRESIEVE_LIMIT: u64 = 1000;
+
+/// How many numbers each thread handles at a time in [count_primes_sieve_parallel_u128].
+const PRESIEVE_SEGMENT: u128 = 1 << 16;
+
+/// Count the primes in `[low, high)` for one thread, crossing off multiples of `small_primes` a
+/// segment at a time and only testing the survivors.
+fn count_presieved(low: u128, high: u128, small_primes: &[u64]) -> u128 {
+    let mut count = 0;
+    let mut seg_low = low;
+    while seg_low < high {
+        let seg_high = high.min(seg_low.saturating_add(PRESIEVE_SEGMENT));
+        let mut candidate = vec![true; (seg_high - seg_low) as usize];
+        for &p in small_primes {
+            let p = p as u128;
+            // Cross off multiples of p, but not p itself
+            let first = (p * p).max(seg_low.div_ceil(p) * p);
+            let mut multiple = first;
+            while multiple < seg_high {
+                candidate[(multiple - seg_low) as usize] = false;
+                multiple += p;
+            }
+        }
+        for (i, _) in candidate.iter().enumerate().filter(|(_, &c)| c) {
+            let n = seg_low + i as u128;
+            if n >= 2 && (n < PRESIEVE_LIMIT as u128 || is_prime_u128(n)) {
+                count += 1;
+            }
+        }
+        seg_low = seg_high;
+    }
+    count
+}
+
+/// The same count as [count_primes_range_u128], but much quicker for long ranges: the range is
+/// split between one thread per core, and each thread crosses off the multiples of the primes up
+/// to 1000 before running Miller-Rabin on whatever's left. That removes about 92% of the numbers
+/// without ever testing them.
+pub fn count_primes_sieve_parallel_u128(low: u128, high: u128) -> u128 {
+    if low >= high {
+        return 0;
+    }
+    let small_primes = sieve_primes(PRESIEVE_LIMIT - 1);
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1) as u128;
+    let chunk = (high - low).div_ceil(threads);
+    thread::scope(|scope| {
+        let handles = (0..threads)
+            .map(|i| (low + i * chunk, high.min(low + (i + 1) * chunk)))
+            .filter(|(lo, hi)| lo < hi)
+            .map(|(lo, hi)| {
+                let small_primes = &small_primes;
+                scope.spawn(move || count_presieved(lo, hi, small_primes))
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().map(|h| h.join().expect("A counting thread panicked")).sum()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_TO_THE_64: u128 = 1 << 64;
+
+    #[test]
+    fn mersenne_127_is_prime() {
+        assert!(is_prime_u128((1 << 127) - 1));
+        assert!(!is_prime_u128((1 << 127) - 3));
+    }
+
+    #[test]
+    fn products_of_big_primes_are_composite() {
+        let (p, q) = ((1u128 << 61) - 1, (1u128 << 31) - 1);
+        assert!(is_prime_u128(p) && is_prime_u128(q));
+        assert!(!is_prime_u128(p * q));
+        assert!(!is_prime_u128(p * p));
+    }
+
+    #[test]
+    fn first_prime_past_u64() {
+        let first = (TWO_TO_THE_64..).find(|&n| is_prime_u128(n));
+        assert_eq!(first, Some(TWO_TO_THE_64 + 13));
+        assert_eq!((1u128 << 100..).find(|&n| is_prime_u128(n)), Some((1 << 100) + 277));
+    }
+
+    #[test]
+    fn counts_agree() {
+        assert_eq!(count_primes_range_u128(0, 1000), 168);
+        assert_eq!(count_primes_sieve_parallel_u128(0, 1000), 168);
+        let (low, high) = (TWO_TO_THE_64, TWO_TO_THE_64 + 10_000);
+        assert_eq!(count_primes_range_u128(low, high), 210);
+        assert_eq!(count_primes_sieve_parallel_u128(low, high), 210);
+    }
+}
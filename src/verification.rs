@@ -0,0 +1,135 @@
+//! Checking search results against the sieve, and against the published list of primes.
+
+use crate::sieve::{first_primes, sieve_primes};
+use crate::tables::OEIS_A000040_FIRST_1000;
+use crate::PrimeResult;
+use std::error::Error;
+use std::fmt;
+
+/// The first place a sequence of primes went wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationError {
+    /// The position in the sequence of the bad value
+    pub index: usize,
+    /// The prime that should have been there
+    pub expected: u64,
+    /// The value that was there instead
+    pub got: u64,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {} at index {}, but got {}", self.expected, self.index, self.got)
+    }
+}
+
+impl Error for VerificationError {}
+
+/// Check that `primes` is the sequence of primes starting from 2, i.e. that `primes[i]` is the
+/// `(i+1)`th prime.
+pub fn verify_prime_sequence(primes: &[u64]) -> Result<(), VerificationError> {
+    let truth = first_primes(primes.len());
+    match primes.iter().zip(truth.iter()).position(|(got, expected)| got != expected) {
+        Some(index) => Err(VerificationError { index, expected: truth[index], got: primes[index] }),
+        None => Ok(()),
+    }
+}
+
+/// Check that `value` is the `n`th prime. If it is, then it's prime, and there are exactly `n`
+/// primes up to and including it, so we only have to sieve as far as `value` itself.
+pub fn verify_nth_prime(n: u64, value: u64) -> bool {
+    let primes = sieve_primes(value);
+    primes.len() as u64 == n && primes.last() == Some(&value)
+}
+
+/// Check every result with a single sieve, and print which ones were right.
+pub fn print_verification(results: &[PrimeResult]) {
+    let limit = results.iter().map(|r| r.value).max().unwrap_or(0);
+    let primes = sieve_primes(limit);
+    let mut results = results.iter().collect::<Vec<_>>();
+    results.sort_by_key(|r| r.id);
+    for r in results {
+        let correct = r.n > 0 && primes.get(r.n as usize - 1) == Some(&r.value);
+        println!("#{:2}, {:6}th prime = {:12} {}", r.id, r.n, r.value, if correct { "OK" } else { "WRONG" });
+    }
+}
+
+/// How a set of results compared with [OEIS_A000040_FIRST_1000].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerificationReport {
+    /// How many results were the right prime
+    pub correct: usize,
+    /// `(n, expected, got)` for every result that wasn't
+    pub incorrect: Vec<(u64, u64, u64)>,
+}
+
+impl VerificationReport {
+    /// How many results were checked. Results for `n` past the end of the table aren't counted.
+    pub fn checked(&self) -> usize {
+        self.correct + self.incorrect.len()
+    }
+}
+
+impl fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} results match OEIS A000040", self.correct, self.checked())?;
+        for (n, expected, got) in &self.incorrect {
+            write!(f, "\n  the {}th prime is {}, not {}", n, expected, got)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check every result for `n` from 1 to 1000 against [OEIS_A000040_FIRST_1000]. The rest are left
+/// out of the report altogether; use [print_verification] for those.
+pub fn verify_against_known_primes(results: &[PrimeResult]) -> VerificationReport {
+    let mut report = VerificationReport::default();
+    for r in results.iter().filter(|r| r.n > 0 && r.n as usize <= OEIS_A000040_FIRST_1000.len()) {
+        let expected = OEIS_A000040_FIRST_1000[r.n as usize - 1];
+        if r.value == expected {
+            report.correct += 1;
+        } else {
+            report.incorrect.push((r.n, expected, r.value));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn_prime_output;
+    use futures::future::join_all;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn correct_sequence_passes() {
+        assert_eq!(verify_prime_sequence(&[2, 3, 5, 7, 11, 13]), Ok(()));
+        assert_eq!(verify_prime_sequence(&[]), Ok(()));
+    }
+
+    #[test]
+    fn incorrect_sequence_fails_at_the_bad_value() {
+        let error = verify_prime_sequence(&[2, 3, 5, 9, 11, 13]).unwrap_err();
+        assert_eq!(error, VerificationError { index: 3, expected: 7, got: 9 });
+    }
+
+    #[test]
+    fn first_ten_searches_match_oeis() {
+        let results = crate::prime_test! {
+            join_all((1..=10).map(|n| spawn_prime_output(n, n))).await
+        };
+        let report = verify_against_known_primes(&results);
+        assert_eq!(report, VerificationReport { correct: 10, incorrect: Vec::new() });
+    }
+
+    #[test]
+    fn wrong_results_are_reported_and_unknown_ones_skipped() {
+        let result = |n, value| {
+            PrimeResult { id: 0, n, value, started_at: Instant::now(), elapsed: Duration::ZERO, peak_memory: 0 }
+        };
+        let report = verify_against_known_primes(&[result(4, 7), result(5, 9), result(1001, 7933), result(0, 0)]);
+        assert_eq!(report, VerificationReport { correct: 1, incorrect: vec![(5, 11, 9)] });
+        assert_eq!(report.checked(), 2);
+    }
+}
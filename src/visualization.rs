@@ -0,0 +1,208 @@
+//! Pictures of primes, drawn in ASCII.
+
+use futures::future::poll_fn;
+use crate::primality::is_prime_fast;
+use crate::sieve::sieve_primes;
+use crate::PrimeResult;
+use std::time::{Duration, Instant};
+use tokio_executor::threadpool::blocking;
+
+/// The Ulam spiral: write the numbers 1, 2, 3, ... in a square spiral out from the centre, and mark
+/// the primes with `#` and everything else with `.`. The primes bunch up along diagonal lines,
+/// which nobody has fully explained.
+///
+/// The spiral starts in the middle of the grid and winds anticlockwise, starting to the right:
+/// ```text
+/// 17 16 15 14 13
+/// 18  5  4  3 12
+/// 19  6  1  2 11
+/// 20  7  8  9 10
+/// 21 22 23 24 25
+/// ```
+pub fn ulam_spiral(size: usize) -> Vec<Vec<char>> {
+    let mut grid = vec![vec!['.'; size]; size];
+    if size == 0 {
+        return grid;
+    }
+    // For even sizes there's no true centre, so start just up and to the left of it
+    let (mut x, mut y) = ((size as i64 - 1) / 2, size as i64 / 2);
+    let directions = [(1, 0), (0, -1), (-1, 0), (0, 1)];
+    let (mut n, mut placed) = (1u64, 0);
+    let mut leg = 0;
+    // The legs of the spiral are 1, 1, 2, 2, 3, 3, ... steps long. For even sizes, some of the legs
+    // run off the edge of the grid, so we keep walking until every cell has been filled.
+    while placed < size * size {
+        let (dx, dy) = directions[leg % 4];
+        for _ in 0..leg / 2 + 1 {
+            if x >= 0 && y >= 0 && (x as usize) < size && (y as usize) < size {
+                if is_prime_fast(n) {
+                    grid[y as usize][x as usize] = '#';
+                }
+                placed += 1;
+            }
+            n += 1;
+            x += dx;
+            y += dy;
+        }
+        leg += 1;
+    }
+    grid
+}
+
+/// Print a `size` x `size` Ulam spiral to stdout.
+pub fn print_spiral(size: usize) {
+    for row in ulam_spiral(size) {
+        println!("{}", row.into_iter().collect::<String>());
+    }
+}
+
+/// Draw and print the spiral on a blocking thread. Big spirals take a while to draw.
+pub async fn print_spiral_blocking(size: usize) {
+    poll_fn(|_| blocking(|| print_spiral(size))).await.expect("Couldn't block")
+}
+
+/// Render a horizontal bar chart, one bar per bucket. `buckets[i]` is the inclusive range of values
+/// in the `i`th bucket, and `counts[i]` is how many values fell into it. The biggest count gets a bar
+/// `width` characters long, and the others are scaled to match.
+pub fn prime_ascii_histogram(buckets: &[(u64, u64)], counts: &[u64], width: usize) -> String {
+    let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+    let label_width = buckets.iter().map(|(lo, hi)| format!("{}-{}", lo, hi).len()).max().unwrap_or(0);
+    let mut chart = String::new();
+    for (&(lo, hi), &count) in buckets.iter().zip(counts) {
+        let bar = "#".repeat((count as usize * width + max_count as usize / 2) / max_count as usize);
+        let label = format!("{}-{}", lo, hi);
+        chart.push_str(&format!("{:>w$} | {} {}\n", label, bar, count, w = label_width));
+    }
+    chart
+}
+
+/// Count the gaps between consecutive primes in `low..=high`, grouped into `bucket_count` equally
+/// wide buckets spanning the smallest gap to the largest. Returns the buckets as inclusive ranges,
+/// along with the number of gaps in each.
+pub fn prime_gap_buckets(low: u64, high: u64, bucket_count: usize) -> (Vec<(u64, u64)>, Vec<u64>) {
+    let primes = sieve_primes(high);
+    let start = primes.partition_point(|&p| p < low);
+    let gaps = primes[start..].windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+    let (min, max) = match (gaps.iter().min(), gaps.iter().max()) {
+        (Some(&min), Some(&max)) if bucket_count > 0 => (min, max),
+        _ => return (Vec::new(), Vec::new()),
+    };
+    let bucket_width = (max - min) / bucket_count as u64 + 1;
+    let buckets = (0..bucket_count as u64)
+        .map(|i| (min + i * bucket_width, min + (i + 1) * bucket_width - 1))
+        .collect::<Vec<_>>();
+    let mut counts = vec![0; bucket_count];
+    for gap in gaps {
+        counts[((gap - min) / bucket_width) as usize] += 1;
+    }
+    (buckets, counts)
+}
+
+/// A bar chart of how far apart the primes in `low..=high` are.
+pub fn prime_gap_histogram(low: u64, high: u64, bucket_count: usize) -> String {
+    let (buckets, counts) = prime_gap_buckets(low, high, bucket_count);
+    prime_ascii_histogram(&buckets, &counts, 60)
+}
+
+/// A Gantt chart of `results`, one row per search, in the same order: `.` for the time between
+/// `wall_start` and the search starting on its blocking thread, then `=` for the search itself, in
+/// brackets.
+/// ```text
+/// task  0: [=================================]
+/// task  5: ......[=====================]
+/// task 14: ..............................[===]
+/// ```
+/// The whole run, from `wall_start` to the last search finishing, is scaled to `width` columns.
+/// Every search gets at least one `=`, however quick it was, so that it shows up.
+pub fn task_timeline(results: &[PrimeResult], wall_start: Instant, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let waited = |r: &PrimeResult| r.started_at.saturating_duration_since(wall_start);
+    let total = results.iter().map(|r| waited(r) + r.elapsed).max().unwrap_or_default();
+    let columns = |d: Duration| {
+        if total.is_zero() {
+            0
+        } else {
+            (d.as_secs_f64() / total.as_secs_f64() * width as f64).round() as usize
+        }
+    };
+    results
+        .iter()
+        .map(|r| {
+            let ran = columns(waited(r) + r.elapsed).clamp(1, width);
+            let waited = columns(waited(r)).min(ran - 1);
+            format!("task {:2}: {}[{}]", r.id, ".".repeat(waited), "=".repeat(ran - waited))
+        })
+        .collect()
+}
+
+/// Print [task_timeline] to stdout.
+pub fn print_task_timeline(results: &[PrimeResult], wall_start: Instant, width: usize) {
+    for row in task_timeline(results, wall_start, width) {
+        println!("{}", row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::join_all;
+
+    fn rows(grid: Vec<Vec<char>>) -> Vec<String> {
+        grid.into_iter().map(|row| row.into_iter().collect()).collect()
+    }
+
+    #[test]
+    fn five_by_five_spiral() {
+        let grid = ulam_spiral(5);
+        // 2, 3, 5, 7, 11, 13, 17, 19 and 23 are the primes up to 25
+        assert_eq!(grid.iter().flatten().filter(|&&c| c == '#').count(), 9);
+        assert_eq!(rows(grid), vec!["#...#", ".#.#.", "#..##", ".#...", "..#.."]);
+    }
+
+    #[test]
+    fn even_spirals_are_filled() {
+        // 4 3
+        // 1 2
+        assert_eq!(rows(ulam_spiral(2)), vec![".#", ".#"]);
+        assert_eq!(ulam_spiral(10).iter().flatten().filter(|&&c| c == '#').count(), 25);
+        assert!(ulam_spiral(0).is_empty());
+    }
+
+    #[test]
+    fn histogram_has_a_line_per_bucket() {
+        let buckets = [(1, 2), (3, 4), (5, 6), (7, 8)];
+        let chart = prime_ascii_histogram(&buckets, &[3, 12, 6, 0], 20);
+        let lines = chart.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        let bar = |line: &str| line.chars().filter(|&c| c == '#').count();
+        assert_eq!(lines.iter().map(|l| bar(l)).collect::<Vec<_>>(), vec![5, 20, 10, 0]);
+        assert_eq!(lines[1], "3-4 | #################### 12");
+    }
+
+    #[test]
+    fn timeline_scales_the_run_to_the_width() {
+        let wall_start = Instant::now();
+        let result = |id, start, secs| {
+            let (started_at, elapsed) = (wall_start + Duration::from_secs(start), Duration::from_secs(secs));
+            PrimeResult { id, n: 1, value: 2, started_at, elapsed, peak_memory: 0 }
+        };
+        let results = [result(0, 0, 10), result(1, 5, 5), result(12, 10, 0)];
+        let expected = ["task  0: [==========]", "task  1: .....[=====]", "task 12: .........[=]"];
+        assert_eq!(task_timeline(&results, wall_start, 10), expected);
+        assert!(task_timeline(&[], wall_start, 10).is_empty());
+    }
+
+    #[test]
+    fn timeline_has_a_row_for_every_search() {
+        let wall_start = Instant::now();
+        let results = crate::prime_test! {
+            join_all((0..20).map(|id| crate::spawn_prime_output(id, 100 + id * 50))).await
+        };
+        let timeline = task_timeline(&results, wall_start, 40);
+        assert_eq!(timeline.len(), 20);
+        for (id, row) in timeline.iter().enumerate() {
+            assert!(row.starts_with(&format!("task {:2}: ", id)), "{}", row);
+            assert!(row.contains("[=") && row.ends_with("=]"), "{}", row);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! The batch API, used the way its docs recommend: straight from `block_on`.
+
+use async_await::tables::OEIS_A000040_FIRST_1000;
+use async_await::testing::test_runtime;
+use async_await::{prime_output_batch, PrimeResult};
+
+/// The `(id, value)` of each result, in the order they came back.
+fn ids_and_values(results: &[PrimeResult]) -> Vec<(u64, u64)> {
+    results.iter().map(|r| (r.id, r.value)).collect()
+}
+
+fn nth_prime(n: u64) -> u64 {
+    OEIS_A000040_FIRST_1000[n as usize - 1]
+}
+
+#[test]
+fn batch_of_one() {
+    let results = test_runtime(2).block_on(prime_output_batch(vec![(7, 10)], 4));
+    assert_eq!(ids_and_values(&results), vec![(7, 29)]);
+}
+
+#[test]
+fn more_room_than_searches() {
+    let pairs = vec![(2, 1000), (0, 10), (1, 100)];
+    let results = test_runtime(2).block_on(prime_output_batch(pairs, 10));
+    assert_eq!(ids_and_values(&results), vec![(0, 29), (1, 541), (2, 7919)]);
+}
+
+#[test]
+fn far_more_searches_than_room() {
+    // Submitted in reverse, so sorting by id is what puts them in order
+    let pairs: Vec<_> = (0..100).rev().map(|id| (id, 1000 - id * 7)).collect();
+    for max_concurrent in [0, 2] {
+        let results = test_runtime(2).block_on(prime_output_batch(pairs.clone(), max_concurrent));
+        let expected: Vec<_> = (0..100).map(|id| (id, nth_prime(1000 - id * 7))).collect();
+        assert_eq!(ids_and_values(&results), expected);
+    }
+}
@@ -0,0 +1,19 @@
+//! The example binaries take far too long to run in a test, but they should at least build.
+//! Cargo builds every binary before running the integration tests, and tells us where it put them.
+
+use std::path::Path;
+
+#[test]
+fn single_thread_demo_builds() {
+    assert!(Path::new(env!("CARGO_BIN_EXE_single_thread")).is_file());
+}
+
+#[test]
+fn batch_demo_builds() {
+    assert!(Path::new(env!("CARGO_BIN_EXE_batch")).is_file());
+}
+
+#[test]
+fn rayon_comparison_builds() {
+    assert!(Path::new(env!("CARGO_BIN_EXE_rayon_vs_tokio")).is_file());
+}
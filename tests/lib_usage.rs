@@ -0,0 +1,22 @@
+//! Using the crate the way another crate would: only through its public API.
+
+use async_await::analytic::legendre_pi;
+use async_await::primality::is_prime_fast;
+use async_await::testing::test_runtime;
+use async_await::{run_demo, DemoConfig};
+
+#[test]
+fn default_demo_finds_every_prime_in_order() {
+    let config = DemoConfig::default();
+    let tasks = config.tasks();
+    // `run_demo` spawns the searches as soon as it's called, so call it inside the runtime
+    let results = test_runtime(4).block_on(async { run_demo(config).await });
+    assert_eq!(results.len(), 20);
+    for (result, (id, n)) in results.iter().zip(tasks) {
+        assert_eq!((result.id, result.n), (id, n));
+        // Sieving this far for every result would take longer than the demo, so count instead
+        let nth_prime = is_prime_fast(result.value) && legendre_pi(result.value) == n;
+        assert!(nth_prime, "{} isn't the {}th prime", result.value, n);
+    }
+    assert_eq!(results[0].value, 86_028_121);
+}
@@ -0,0 +1,48 @@
+//! Memory use of the sieves and searches, as jemalloc sees it.
+//!
+//! A [MemoryTracker] only sees the thread it was started on, but [measure_allocation] sees the
+//! whole process, so these tests take turns.
+
+use async_await::memory::{measure_allocation, MemoryTracker};
+use async_await::primality::SIEVE_THRESHOLD;
+use async_await::sieve::{sieve_primes, BitSieve, Sieve};
+use async_await::spawn_prime_output;
+use async_await::testing::test_runtime;
+use std::sync::Mutex;
+
+const LIMIT: u64 = 10_000_000;
+const PRIMES_BELOW_LIMIT: usize = 664_579;
+
+/// Held by each test while it runs.
+static ALONE: Mutex<()> = Mutex::new(());
+
+#[test]
+fn searches_that_allocate_have_a_peak() {
+    let _alone = ALONE.lock().unwrap();
+    // Searches up to `SIEVE_THRESHOLD` sieve; the ones past it hardly allocate at all
+    let result = test_runtime(1).block_on(async { spawn_prime_output(0, SIEVE_THRESHOLD).await });
+    assert_eq!(result.value, 104_729);
+    assert!(result.peak_memory > 0);
+}
+
+#[test]
+fn sieve_primes_only_keeps_its_primes() {
+    let _alone = ALONE.lock().unwrap();
+    let (primes, kept) = measure_allocation(|| sieve_primes(LIMIT));
+    assert_eq!(primes.len(), PRIMES_BELOW_LIMIT);
+    // The list itself is 5MB, in 8MB of capacity. The 10MB table it was sieved in should be gone.
+    let list = primes.capacity() * std::mem::size_of::<u64>();
+    assert!(kept <= list + 500_000, "kept {} bytes for a list of {}", kept, list);
+}
+
+#[test]
+fn bit_sieve_needs_a_bit_per_odd_number() {
+    let _alone = ALONE.lock().unwrap();
+    let tracker = MemoryTracker::start();
+    let (count, kept) = measure_allocation(|| BitSieve.count_up_to(LIMIT));
+    assert_eq!(count, PRIMES_BELOW_LIMIT as u64);
+    // 625kB of bits, and jemalloc's peak is only good to about 100kB
+    assert!(tracker.peak_usage() <= 1_500_000, "peaked at {} bytes", tracker.peak_usage());
+    // None of which is still around afterwards
+    assert!(kept < 100_000, "kept {} bytes", kept);
+}
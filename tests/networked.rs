@@ -0,0 +1,67 @@
+//! A networked search against workers running on this machine.
+
+use async_await::networked::{
+    find_nth_prime_networked, prime_worker_server, serve_segment_requests, try_find_nth_prime_networked,
+};
+use async_await::testing::test_runtime;
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// Start `count` workers on ports the OS picks, and return their addresses. The listeners are
+/// bound before this returns, so the workers are ready as soon as their tasks get polled.
+async fn spawn_workers(count: usize) -> Vec<SocketAddr> {
+    let mut addrs = Vec::new();
+    for _ in 0..count {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        addrs.push(listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            serve_segment_requests(listener).await.unwrap();
+        });
+    }
+    addrs
+}
+
+/// An address nothing is listening on, or at least wasn't a moment ago.
+fn unused_addr() -> SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+}
+
+#[test]
+fn three_workers_find_the_10000th_prime() {
+    test_runtime(4).block_on(async {
+        let workers = spawn_workers(3).await;
+        assert_eq!(find_nth_prime_networked(10_000, workers.clone()).await, 104_729);
+        assert_eq!(try_find_nth_prime_networked(1, workers).await.unwrap(), 2);
+    });
+}
+
+#[test]
+fn bad_searches_are_errors() {
+    test_runtime(4).block_on(async {
+        let workers = spawn_workers(1).await;
+        let error = try_find_nth_prime_networked(0, workers).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let error = try_find_nth_prime_networked(10, Vec::new()).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    });
+}
+
+#[test]
+fn unreachable_workers_are_errors() {
+    test_runtime(4).block_on(async {
+        let mut workers = spawn_workers(2).await;
+        workers.push(unused_addr());
+        let error = try_find_nth_prime_networked(10_000, workers).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    });
+}
+
+#[test]
+fn workers_report_addresses_they_cant_listen_on() {
+    test_runtime(1).block_on(async {
+        let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let error = prime_worker_server(taken.local_addr().unwrap()).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+    });
+}
@@ -0,0 +1,44 @@
+//! Every primality test, checked against the shared test vectors.
+
+use async_await::is_prime;
+use async_await::primality::{baillie_psw, is_prime_fast, is_prime_wheel, miller_rabin, WITNESSES};
+use async_await::test_vectors::{generate_prime_test_vectors, STRONG_PSEUDOPRIMES};
+
+/// Trial division needs up to `√n` divisions, so only give it the vectors it can get through in a
+/// sensible time.
+const TRIAL_DIVISION_LIMIT: u64 = 1 << 40;
+
+fn check(name: &str, is_prime: fn(u64) -> bool, limit: u64) {
+    for (n, expected) in generate_prime_test_vectors().into_iter().filter(|&(n, _)| n <= limit) {
+        assert_eq!(is_prime(n), expected, "{} got {} wrong", name, n);
+    }
+}
+
+#[test]
+fn trial_division() {
+    check("is_prime", is_prime, TRIAL_DIVISION_LIMIT);
+}
+
+#[test]
+fn wheel() {
+    check("is_prime_wheel", is_prime_wheel, TRIAL_DIVISION_LIMIT);
+}
+
+#[test]
+fn miller_rabin_every_witness() {
+    check("miller_rabin", |n| miller_rabin(n, &WITNESSES), u64::MAX);
+    check("is_prime_fast", is_prime_fast, u64::MAX);
+}
+
+#[test]
+fn bpsw() {
+    check("baillie_psw", baillie_psw, u64::MAX);
+}
+
+#[test]
+fn pseudoprimes_fool_too_few_witnesses() {
+    // 2047 = 23 * 89 is the smallest strong pseudoprime to base 2
+    assert!(miller_rabin(2047, &[2]));
+    assert!(!miller_rabin(2047, &[2, 3]));
+    assert!(STRONG_PSEUDOPRIMES.iter().all(|&n| !miller_rabin(n, &WITNESSES)));
+}
@@ -0,0 +1,52 @@
+//! Searches log inside the span they were started from, even once they've been spawned.
+//!
+//! The blocking closure runs on a pool thread, so a thread-local subscriber wouldn't see it. This
+//! test installs a global one, which is why it has a test binary to itself.
+
+use async_await::spawn_with_handle;
+use async_await::testing::test_runtime;
+use async_await::traced::prime_output_with_tracing_context;
+use std::io;
+use std::sync::{Arc, Mutex};
+use tracing::{info_span, Span};
+
+/// Everything the subscriber writes, shared with the test.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn spawned_searches_log_inside_the_callers_span() {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt().with_writer(move || writer.clone()).with_ansi(false).finish();
+    tracing::subscriber::set_global_default(subscriber).expect("A subscriber was already set");
+
+    test_runtime(2).block_on(async {
+        let request = info_span!("request", request_id = 7);
+        // Spawned while the request span is entered, but not instrumented with it, so only the
+        // span passed in can tell the search where it belongs
+        let carried = request.in_scope(|| spawn_with_handle(prime_output_with_tracing_context(1, 10, request.clone())));
+        assert_eq!(carried.await.value, 29);
+        let lost = request.in_scope(|| spawn_with_handle(prime_output_with_tracing_context(2, 10, Span::none())));
+        assert_eq!(lost.await.value, 29);
+    });
+
+    let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    let lines = |id| output.lines().filter(move |line| line.contains(&format!("id={}", id))).collect::<Vec<_>>();
+    let (carried, lost) = (lines(1), lines(2));
+    assert_eq!(carried.len(), 2, "{}", output);
+    assert!(carried.iter().all(|line| line.contains("request{request_id=7}")), "{}", output);
+    assert_eq!(lost.len(), 2, "{}", output);
+    assert!(lost.iter().all(|line| !line.contains("request{")), "{}", output);
+}